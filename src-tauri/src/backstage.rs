@@ -1,6 +1,14 @@
-use crate::{kv, manager::TASKS, model::RecordStatus};
+use crate::{
+    condition, kv, library,
+    manager::{inner, record, TASKS},
+    model::{LiveSample, LiveStatus, RecordStatus, RecordingPlan, RecordingStrategy},
+    platform,
+};
 use std::time::Duration;
 
+/// 录制时长少于这个毫秒数就退出的，不自动重新开始，避免流有问题时反复重启
+pub const MIN_SESSION_MILLIS_FOR_RESTART: i64 = 10_000;
+
 // 在新线程中初始化
 pub fn init_with_new_thread() {
     std::thread::spawn(|| {
@@ -18,6 +26,9 @@ async fn init() {
     println!("后台检查任务已运行");
     check_recording_histories().await;
     tokio::spawn(check_tasks_loop());
+    tokio::spawn(check_recordings_loop());
+    tokio::spawn(backup_loop());
+    tokio::spawn(purge_trash_loop());
     // 无限循环阻塞
     loop {
         tokio::time::sleep(Duration::from_secs(60)).await;
@@ -43,7 +54,7 @@ pub async fn check_recording_histories() {
             continue;
         }
         // 否则，更新数据库记录
-        kv::history::end(&history.url).unwrap_or_else(|e| {
+        library::end_recording(&history.url).unwrap_or_else(|e| {
             eprintln!("end_recording_history error: {}", e);
         });
     }
@@ -59,6 +70,94 @@ pub async fn check_tasks_loop() {
     }
 }
 
+/// 按照开播信息检查间隔，检查正在录制的直播间：记录观看人数和标题，检查是否还满足录制条件
+pub async fn check_recordings_loop() {
+    loop {
+        let interval = kv::config::get()
+            .map(|config| config.live_info_check_interval)
+            .unwrap_or(60);
+        tokio::time::sleep(Duration::from_secs(interval.max(1))).await;
+        let urls: Vec<String> = TASKS.iter().map(|task| task.key().clone()).collect();
+        for url in urls {
+            if let Err(e) = check_recording(&url).await {
+                eprintln!("check_recording error for url {}: {}", url, e);
+            }
+        }
+    }
+}
+
+/// 每隔 10 分钟查看最新的数据库备份，超过配置的备份间隔就创建一个新的
+pub async fn backup_loop() {
+    loop {
+        if let Err(e) = backup_if_due() {
+            eprintln!("backup error: {}", e);
+        }
+        tokio::time::sleep(Duration::from_secs(600)).await;
+    }
+}
+
+fn backup_if_due() -> anyhow::Result<()> {
+    let interval = kv::config::get()?.backup_interval;
+    if interval == 0 {
+        return Ok(());
+    }
+    let latest = kv::backup::list()?.first().map(|backup| backup.created_at);
+    let now = chrono::Utc::now().timestamp_millis();
+    if latest.is_none_or(|latest| now - latest >= interval as i64 * 3_600_000) {
+        let backup = kv::backup::create()?;
+        println!("数据库已备份到 {}", backup.path);
+    }
+    Ok(())
+}
+
+/// 每隔 1 小时彻底删除回收站中超过保留天数的录制
+pub async fn purge_trash_loop() {
+    loop {
+        let retention_days = kv::config::get()
+            .map(|config| config.trash_retention_days)
+            .unwrap_or(0);
+        if retention_days > 0 {
+            match library::purge_trash(retention_days) {
+                Ok(0) => {}
+                Ok(purged) => println!("已彻底删除回收站中的 {} 个录制", purged),
+                Err(e) => eprintln!("purge_trash error: {}", e),
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(3600)).await;
+    }
+}
+
+// 记录一次直播间的状态，标题变化时重新检查录制条件，不满足就停止录制
+// 只有后端有解析器的平台才能检查，其它平台只在开播时检查
+async fn check_recording(url: &str) -> anyhow::Result<()> {
+    let Some(resolver) = platform::find_resolver(url) else {
+        return Ok(());
+    };
+    let Some(history) = kv::history::get_recording(url)? else {
+        return Ok(());
+    };
+    let previous_title = kv::live::get(url)?.map(|info| info.title);
+    let info = platform::resolve(resolver.as_ref(), url).await?;
+    if info.status != LiveStatus::Live {
+        return Ok(());
+    }
+    kv::sample::add(url, history.start_time, &LiveSample::new(&info))?;
+    if previous_title.as_ref() == Some(&info.title) {
+        return Ok(());
+    }
+    let Some(plan) = kv::plan::get(url.to_string())? else {
+        return Ok(());
+    };
+    if plan.conditions.is_empty() {
+        return Ok(());
+    }
+    if let Some(reason) = condition::check(&plan.conditions, &info)? {
+        println!("标题变化后不满足录制条件，停止录制：{}，{}", url, reason);
+        record::stop_record(url).await.map_err(anyhow::Error::msg)?;
+    }
+    Ok(())
+}
+
 // 查看 TASKS 中的录制任务是否还在运行，如果不是，就从 TASKS 中移除，并更新数据库记录
 pub async fn check_tasks() -> anyhow::Result<()> {
     if TASKS.is_empty() {
@@ -99,10 +198,84 @@ pub async fn check_tasks() -> anyhow::Result<()> {
             );
             eprintln!("任务已退出：{}", error_message);
         }
+        let previous = kv::history::get_recording(&url).unwrap_or_else(|e| {
+            eprintln!("get_recording_history error: {}", e);
+            None
+        });
         // 更新数据库记录
-        kv::history::end(&url).unwrap_or_else(|e| {
+        library::end_recording(&url).unwrap_or_else(|e| {
             eprintln!("end_recording_history error: {}", e);
         });
+        // 不是手动停止的录制，如果计划还需要录制并且主播还在直播，用新的流地址重新开始
+        if let Some(previous) = previous {
+            let now = chrono::Utc::now().timestamp_millis();
+            let plan = kv::plan::get(previous.url.clone()).unwrap_or_else(|e| {
+                eprintln!("get plan error: {}", e);
+                None
+            });
+            let backend_resolvable = platform::find_resolver(&previous.url).is_some();
+            if now - previous.start_time >= MIN_SESSION_MILLIS_FOR_RESTART
+                && should_restart(plan.as_ref(), backend_resolvable, now)
+            {
+                tokio::spawn(async move {
+                    if let Err(e) = inner::restart_record(&previous).await {
+                        eprintln!("restart_record error for url {}: {}", previous.url, e);
+                    }
+                });
+            }
+        }
     }
     Ok(())
 }
+
+/// 录制意外结束后，后端是否应该立刻重新开始
+/// 手动开始的一次性录制没有计划，不重新开始；计划被禁用、定时录制已过结束时间（毫秒时间戳）、
+/// 限定了录制时长的，都不重新开始，避免用户或者时长已经结束的录制又被开始
+/// 前端解析的平台后端拿不到新的流地址，这里不处理，由前端轮询在下次检查到开播时重新开始
+fn should_restart(plan: Option<&RecordingPlan>, backend_resolvable: bool, now: i64) -> bool {
+    let Some(plan) = plan else {
+        return false;
+    };
+    if !plan.enabled || !backend_resolvable {
+        return false;
+    }
+    match plan.strategy {
+        RecordingStrategy::Timed(_, end) => now < end,
+        RecordingStrategy::TimedWithDuration(..) | RecordingStrategy::AnchorLiveWithDuration(_) => {
+            false
+        }
+        RecordingStrategy::TimedUntilAnchorEnd(_) | RecordingStrategy::AnchorLive => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_restart() {
+        let now = 1_700_000_000_000;
+        let mut plan = RecordingPlan::new_with_url("https://live.douyin.com/1");
+        assert!(should_restart(Some(&plan), true, now));
+        // 手动开始的录制没有计划
+        assert!(!should_restart(None, true, now));
+        // 前端解析的平台交给前端轮询
+        assert!(!should_restart(Some(&plan), false, now));
+
+        plan.enabled = false;
+        assert!(!should_restart(Some(&plan), true, now));
+        plan.enabled = true;
+
+        plan.strategy = RecordingStrategy::Timed(now - 1000, now + 1000);
+        assert!(should_restart(Some(&plan), true, now));
+        plan.strategy = RecordingStrategy::Timed(now - 2000, now - 1000);
+        assert!(!should_restart(Some(&plan), true, now));
+
+        plan.strategy = RecordingStrategy::TimedWithDuration(now - 1000, 3600);
+        assert!(!should_restart(Some(&plan), true, now));
+        plan.strategy = RecordingStrategy::AnchorLiveWithDuration(3600);
+        assert!(!should_restart(Some(&plan), true, now));
+        plan.strategy = RecordingStrategy::TimedUntilAnchorEnd(now - 1000);
+        assert!(should_restart(Some(&plan), true, now));
+    }
+}
@@ -0,0 +1,161 @@
+use crate::model::{LiveInfo, PlatformKind, RecordCondition};
+use anyhow::{anyhow, bail, Result};
+use regex::{Regex, RegexBuilder};
+
+/// 解析器拿不到分区的平台，这些平台的计划设置分区条件也不会生效，所以不允许设置
+const NO_CATEGORY_PLATFORMS: &[PlatformKind] = &[
+    PlatformKind::Tiktok,
+    PlatformKind::Xiaohongshu,
+    PlatformKind::Douyu,
+    PlatformKind::Kuaishou,
+    PlatformKind::Twitch,
+    PlatformKind::Youtube,
+    PlatformKind::Direct,
+];
+
+// 平台能不能提供分区：自定义平台要定义了分区的提取规则，外部解析器不一定能拿到分区，不能用于判断
+fn provides_category(platform_kind: &PlatformKind) -> bool {
+    match platform_kind {
+        PlatformKind::Custom(name) => crate::platform::custom::has_category_rule(name),
+        PlatformKind::External | PlatformKind::Unknown => false,
+        _ => !NO_CATEGORY_PLATFORMS.contains(platform_kind),
+    }
+}
+
+/// 保存计划前检查录制条件：标题的正则要能编译，分区条件只能用于能拿到分区的平台
+pub fn validate(condition: &RecordCondition, platform_kind: &PlatformKind) -> Result<()> {
+    for pattern in [&condition.title_include, &condition.title_exclude]
+        .into_iter()
+        .filter_map(non_empty)
+    {
+        build_regex(pattern).map_err(|e| anyhow!("invalid title pattern: {}", e))?;
+    }
+    if non_empty(&condition.category).is_some() && !provides_category(platform_kind) {
+        bail!("{} does not provide live category", platform_kind);
+    }
+    Ok(())
+}
+
+/// 检查直播信息是否满足计划的录制条件，不满足时返回原因
+pub fn check(condition: &RecordCondition, info: &LiveInfo) -> Result<Option<String>> {
+    if let Some(pattern) = non_empty(&condition.title_include) {
+        if !is_match(pattern, &info.title)? {
+            return Ok(Some(format!("标题不匹配 {}：{}", pattern, info.title)));
+        }
+    }
+    if let Some(pattern) = non_empty(&condition.title_exclude) {
+        if is_match(pattern, &info.title)? {
+            return Ok(Some(format!(
+                "标题匹配了排除条件 {}：{}",
+                pattern, info.title
+            )));
+        }
+    }
+    if let Some(category) = non_empty(&condition.category) {
+        // 平台没有提供分区时不做限制
+        if !info.category.is_empty() && !info.category.contains(category) {
+            return Ok(Some(format!("分区不匹配 {}：{}", category, info.category)));
+        }
+    }
+    if let Some(min) = condition.min_viewer_count {
+        // 人数未知时不做限制
+        if let Some(count) = parse_viewer_count(&info.viewer_count) {
+            if count < min {
+                return Ok(Some(format!("观看人数 {} 少于 {}", count, min)));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+fn build_regex(pattern: &str) -> Result<Regex> {
+    Ok(RegexBuilder::new(pattern).case_insensitive(true).build()?)
+}
+
+fn is_match(pattern: &str, text: &str) -> Result<bool> {
+    Ok(build_regex(pattern)?.is_match(text))
+}
+
+/// 解析平台给出的观看人数，例如 "1234"、"1,234"、"1.2万"、"3.5k"
+pub fn parse_viewer_count(text: &str) -> Option<u64> {
+    let text = text.trim().replace(',', "");
+    let (number, unit) = match text
+        .char_indices()
+        .find(|(_, c)| !c.is_ascii_digit() && *c != '.')
+    {
+        Some((i, _)) => text.split_at(i),
+        None => (text.as_str(), ""),
+    };
+    let number: f64 = number.parse().ok()?;
+    let multiple = match unit.trim().to_lowercase().as_str() {
+        "" | "人" | "人在看" => 1.0,
+        "k" => 1_000.0,
+        "万" | "w" => 10_000.0,
+        "m" => 1_000_000.0,
+        "亿" => 100_000_000.0,
+        _ => return None,
+    };
+    Some((number * multiple) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::PlatformKind;
+
+    #[test]
+    fn test_parse_viewer_count() {
+        assert_eq!(parse_viewer_count("1234"), Some(1234));
+        assert_eq!(parse_viewer_count("1,234"), Some(1234));
+        assert_eq!(parse_viewer_count("1.2万"), Some(12000));
+        assert_eq!(parse_viewer_count("3.5k"), Some(3500));
+        assert_eq!(parse_viewer_count(""), None);
+    }
+
+    #[test]
+    fn test_check() {
+        let mut info = LiveInfo::new("https://example.com/1", PlatformKind::Unknown);
+        info.title = "今晚比赛直播".into();
+        info.viewer_count = "1.2万".into();
+        let condition = RecordCondition {
+            title_include: Some("比赛".into()),
+            title_exclude: Some("重播".into()),
+            category: None,
+            min_viewer_count: Some(10000),
+        };
+        assert!(check(&condition, &info).unwrap().is_none());
+
+        info.title = "比赛重播".into();
+        assert!(check(&condition, &info).unwrap().is_some());
+
+        info.title = "比赛".into();
+        info.viewer_count = "999".into();
+        assert!(check(&condition, &info).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut condition = RecordCondition {
+            title_include: Some("比赛|决赛".into()),
+            ..Default::default()
+        };
+        assert!(validate(&condition, &PlatformKind::Douyin).is_ok());
+        condition.title_exclude = Some("(重播".into());
+        assert!(validate(&condition, &PlatformKind::Douyin).is_err());
+
+        let condition = RecordCondition {
+            category: Some("游戏".into()),
+            ..Default::default()
+        };
+        assert!(validate(&condition, &PlatformKind::Bilibili).is_ok());
+        assert!(validate(&condition, &PlatformKind::Twitch).is_err());
+        assert!(validate(&condition, &PlatformKind::Direct).is_err());
+        assert!(validate(&condition, &PlatformKind::External).is_err());
+        // 没有加载到这个自定义平台，也就没有分区的提取规则
+        assert!(validate(&condition, &PlatformKind::Custom("not-defined".into())).is_err());
+    }
+}
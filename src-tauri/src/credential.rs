@@ -0,0 +1,306 @@
+use crate::{
+    kv,
+    model::{CredentialStatus, JsonValue, PlatformCredential, PlatformKind},
+    request,
+};
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use reqwest::header::{HeaderMap, HeaderValue, COOKIE, USER_AGENT};
+use reqwest::Url;
+
+/// 系统钥匙串中使用的服务名
+const KEYRING_SERVICE: &str = "liveship";
+
+fn keyring_entry(platform_kind: &PlatformKind, host: &str) -> Result<keyring::Entry> {
+    let user = if host.is_empty() {
+        format!("cookie:{}", platform_kind)
+    } else {
+        format!("cookie:{}:{}", platform_kind, host)
+    };
+    Ok(keyring::Entry::new(KEYRING_SERVICE, &user)?)
+}
+
+/// 外部解析器可以处理任意网站，cookie 必须指定域名，只发给这个域名和它的子域名
+/// 其它平台不区分域名，忽略传入的 host
+fn normalize_host(platform_kind: &PlatformKind, host: &str) -> Result<String> {
+    if *platform_kind != PlatformKind::External {
+        return Ok(String::new());
+    }
+    let host = host.trim().to_lowercase();
+    // 也允许直接填写网址
+    let host = match Url::parse(&host) {
+        Ok(url) if url.has_host() => url.host_str().unwrap_or_default().to_string(),
+        _ => host,
+    };
+    let host = host.trim_start_matches('.').to_string();
+    if host.is_empty() || host.contains(['/', ':', ' ']) {
+        return Err(anyhow!("invalid host for external cookie: {}", host));
+    }
+    Ok(host)
+}
+
+/// 网址的域名是否是 host 本身或者它的子域名
+fn host_matches(url_host: &str, host: &str) -> bool {
+    url_host == host
+        || url_host
+            .strip_suffix(host)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// 内置平台自己的域名，平台的 cookie 只发给这些域名和它们的子域名，不发给 CDN 等第三方
+/// 自定义平台的解析器自己带上 cookie，其它平台没有自己的域名
+fn platform_domains(platform_kind: &PlatformKind) -> &'static [&'static str] {
+    match platform_kind {
+        PlatformKind::Douyin => &["douyin.com"],
+        PlatformKind::Tiktok => &["tiktok.com"],
+        PlatformKind::Xiaohongshu => &["xiaohongshu.com"],
+        PlatformKind::Bilibili => &["bilibili.com"],
+        PlatformKind::Huya => &["huya.com"],
+        PlatformKind::Kuaishou => &["kuaishou.com"],
+        PlatformKind::Douyu => &["douyu.com"],
+        PlatformKind::Twitch => &["twitch.tv"],
+        PlatformKind::Youtube => &["youtube.com"],
+        _ => &[],
+    }
+}
+
+/// 保存平台的 cookie，支持请求头格式 a=1; b=2，也支持浏览器导出的 Netscape cookies.txt 格式
+/// 外部解析器需要指定 cookie 所属的域名
+pub fn set_cookie(
+    platform_kind: &PlatformKind,
+    host: &str,
+    cookies: &str,
+) -> Result<PlatformCredential> {
+    let host = normalize_host(platform_kind, host)?;
+    let (cookie, expires_at) = parse_cookies(cookies);
+    if cookie.is_empty() {
+        return Err(anyhow!("cookie is empty"));
+    }
+    // 确保可以作为请求头使用
+    HeaderValue::from_str(&cookie)?;
+    keyring_entry(platform_kind, &host)?.set_password(&cookie)?;
+    let credential = PlatformCredential {
+        platform_kind: platform_kind.clone(),
+        host,
+        updated_at: Utc::now().timestamp_millis(),
+        expires_at,
+        status: CredentialStatus::Unchecked,
+        checked_at: 0,
+    };
+    kv::credential::set(&credential)?;
+    Ok(credential)
+}
+
+/// 获取内置平台的 cookie，没有保存或者已经过期时返回 None
+pub fn get_cookie(platform_kind: &PlatformKind) -> Option<String> {
+    get_stored_cookie(platform_kind, "")
+}
+
+/// 获取请求这个网址时应该带上的 cookie，网址不属于这个平台时返回 None
+/// 外部解析器只使用域名匹配的 cookie，有多个时使用最具体的那个
+/// 内置平台只在请求平台自己的域名时使用平台的 cookie
+pub fn cookie_for(platform_kind: &PlatformKind, url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let url_host = url.host_str()?.to_lowercase();
+    if *platform_kind != PlatformKind::External {
+        let own = platform_domains(platform_kind)
+            .iter()
+            .any(|domain| host_matches(&url_host, domain));
+        return own.then(|| get_cookie(platform_kind)).flatten();
+    }
+    let credentials = kv::credential::get_all()
+        .map_err(|e| eprintln!("Could not get credentials: {}", e))
+        .ok()?;
+    let host = credentials
+        .into_iter()
+        .filter(|c| c.platform_kind == PlatformKind::External && !c.host.is_empty())
+        .filter(|c| host_matches(&url_host, &c.host))
+        .map(|c| c.host)
+        .max_by_key(|host| host.len())?;
+    get_stored_cookie(platform_kind, &host)
+}
+
+fn get_stored_cookie(platform_kind: &PlatformKind, host: &str) -> Option<String> {
+    let credential = kv::credential::get(platform_kind, host).ok()??;
+    if is_expired(&credential) {
+        return None;
+    }
+    let cookie = keyring_entry(platform_kind, host)
+        .and_then(|entry| entry.get_password().map_err(Into::into));
+    match cookie {
+        Ok(cookie) => Some(cookie),
+        Err(e) => {
+            eprintln!("Could not get cookie for {}: {}", platform_kind, e);
+            None
+        }
+    }
+}
+
+/// 删除平台的 cookie
+pub fn delete_cookie(platform_kind: &PlatformKind, host: &str) -> Result<()> {
+    let host = normalize_host(platform_kind, host)?;
+    match keyring_entry(platform_kind, &host)?.delete_password() {
+        Ok(()) | Err(keyring::Error::NoEntry) => {}
+        Err(e) => return Err(e.into()),
+    }
+    kv::credential::delete(platform_kind, &host)
+}
+
+/// 获取所有凭据信息，已过期的会把状态改为 Expired
+pub fn get_all() -> Result<Vec<PlatformCredential>> {
+    let mut credentials = kv::credential::get_all()?;
+    for credential in &mut credentials {
+        if is_expired(credential) {
+            credential.status = CredentialStatus::Expired;
+        }
+    }
+    Ok(credentials)
+}
+
+/// 校验 cookie 是否还有效，并保存校验结果
+/// 有登录状态接口的平台会请求接口确认，其它平台只检查过期时间
+pub async fn validate(platform_kind: &PlatformKind, host: &str) -> Result<CredentialStatus> {
+    let host = normalize_host(platform_kind, host)?;
+    let mut credential = kv::credential::get(platform_kind, &host)?
+        .ok_or_else(|| anyhow!("no cookie for {}", platform_kind))?;
+    let status = if is_expired(&credential) {
+        CredentialStatus::Expired
+    } else {
+        let cookie = keyring_entry(platform_kind, &host)?.get_password()?;
+        match check_login(platform_kind, &cookie).await? {
+            Some(true) => CredentialStatus::Valid,
+            Some(false) => CredentialStatus::Invalid,
+            None => CredentialStatus::Unchecked,
+        }
+    };
+    credential.status = status.clone();
+    credential.checked_at = Utc::now().timestamp_millis();
+    kv::credential::set(&credential)?;
+    Ok(status)
+}
+
+fn is_expired(credential: &PlatformCredential) -> bool {
+    credential.expires_at > 0 && credential.expires_at <= Utc::now().timestamp_millis()
+}
+
+/// 请求平台的登录状态接口，不支持的平台返回 None
+async fn check_login(platform_kind: &PlatformKind, cookie: &str) -> Result<Option<bool>> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        USER_AGENT,
+        HeaderValue::from_static(
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:109.0) Gecko/20100101 Firefox/115.0",
+        ),
+    );
+    headers.insert(COOKIE, HeaderValue::from_str(cookie)?);
+    match platform_kind {
+        PlatformKind::Bilibili => {
+            let json: JsonValue =
+                request::get("https://api.bilibili.com/x/web-interface/nav", headers)
+                    .await?
+                    .json()
+                    .await?;
+            Ok(Some(json["data"]["isLogin"].as_bool().unwrap_or(false)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// 解析用户输入的 cookie，返回请求头格式的 cookie 和最早的过期时间（毫秒，0 表示未知）
+fn parse_cookies(input: &str) -> (String, i64) {
+    let lines: Vec<&str> = input
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .collect();
+    // Netscape 格式每行有 7 列，用 tab 分隔，#HttpOnly_ 开头的行也是有效的 cookie
+    let is_netscape = lines.iter().any(|l| l.split('\t').count() == 7);
+    if !is_netscape {
+        let cookie = lines.join(" ").trim().trim_end_matches(';').to_string();
+        return (cookie, 0);
+    }
+    let mut pairs = vec![];
+    let mut expires_at = 0;
+    for line in lines {
+        let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+        if line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 7 {
+            continue;
+        }
+        // 过期时间是秒，0 表示会话 cookie
+        let expires = fields[4].parse::<i64>().unwrap_or(0) * 1000;
+        if expires > 0 && (expires_at == 0 || expires < expires_at) {
+            expires_at = expires;
+        }
+        pairs.push(format!("{}={}", fields[5], fields[6]));
+    }
+    (pairs.join("; "), expires_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cookies() {
+        let (cookie, expires_at) = parse_cookies(" SESSDATA=abc; bili_jct=def; \n");
+        assert_eq!(cookie, "SESSDATA=abc; bili_jct=def");
+        assert_eq!(expires_at, 0);
+
+        let netscape = "# Netscape HTTP Cookie File\n\
+            .bilibili.com\tTRUE\t/\tFALSE\t1750000000\tSESSDATA\tabc\n\
+            #HttpOnly_.bilibili.com\tTRUE\t/\tTRUE\t1740000000\tbili_jct\tdef\n";
+        let (cookie, expires_at) = parse_cookies(netscape);
+        assert_eq!(cookie, "SESSDATA=abc; bili_jct=def");
+        assert_eq!(expires_at, 1740000000000);
+    }
+
+    #[test]
+    fn test_external_host() {
+        let external = PlatformKind::External;
+        assert_eq!(
+            normalize_host(&external, " .Example.com ").unwrap(),
+            "example.com"
+        );
+        assert_eq!(
+            normalize_host(&external, "https://live.example.com/room/1").unwrap(),
+            "live.example.com"
+        );
+        assert!(normalize_host(&external, "").is_err());
+        // 内置平台不区分域名
+        assert_eq!(
+            normalize_host(&PlatformKind::Bilibili, "example.com").unwrap(),
+            ""
+        );
+
+        assert!(host_matches("example.com", "example.com"));
+        assert!(host_matches("live.example.com", "example.com"));
+        assert!(!host_matches("badexample.com", "example.com"));
+        assert!(!host_matches("example.com.evil.net", "example.com"));
+    }
+
+    #[test]
+    fn test_platform_domains() {
+        let is_own = |platform_kind: &PlatformKind, host: &str| {
+            platform_domains(platform_kind)
+                .iter()
+                .any(|domain| host_matches(host, domain))
+        };
+        assert!(is_own(&PlatformKind::Bilibili, "api.live.bilibili.com"));
+        assert!(is_own(&PlatformKind::Twitch, "gql.twitch.tv"));
+        // CDN 的域名不是平台自己的
+        assert!(!is_own(
+            &PlatformKind::Twitch,
+            "video-weaver.hkg01.hls.ttvnw.net"
+        ));
+        assert!(!is_own(&PlatformKind::Douyin, "pull-flv-l1.douyincdn.com"));
+        assert!(!is_own(&PlatformKind::Direct, "example.com"));
+        assert!(!is_own(
+            &PlatformKind::Custom("mysite".into()),
+            "mysite.com"
+        ));
+    }
+}
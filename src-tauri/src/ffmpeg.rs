@@ -1,9 +1,14 @@
 use anyhow::anyhow;
 pub use anyhow::Result;
 use ffmpeg_sidecar::download::{download_ffmpeg_package, ffmpeg_download_url, unpack_ffmpeg};
+use std::collections::HashMap;
 use std::process::{Child, Stdio};
 
-use crate::{config::config_dir, kv, model::RecordingOption};
+use crate::{
+    config::config_dir,
+    kv,
+    model::{RecordingOption, UserAgentProfile},
+};
 
 /// 给定 ffmpeg 命令，这里只负责执行
 pub fn execute_ffmpeg_command(ffmpeg_command: Vec<String>) -> Result<Child> {
@@ -63,6 +68,7 @@ pub fn record(
     url: &str,
     filename: &str,
     option: Option<RecordingOption>,
+    headers: &HashMap<String, String>,
 ) -> Result<Child> {
     println!(
         "开始录制：{} -> {}, recording option: {:?}",
@@ -82,6 +88,7 @@ pub fn record(
         url,
         filename,
         option.map(|o| o.use_proxy).unwrap_or_default(),
+        headers,
     );
     cmd.args(&ffmpeg_command);
     let mut child = cmd.spawn()?;
@@ -105,8 +112,22 @@ pub fn record(
     Ok(child)
 }
 
-fn build_ffmpeg_record_command(url: &str, filename: &str, proxy: Option<String>) -> Vec<String> {
-    let user_agent = r#""Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36""#;
+fn strip_line_breaks(value: &str) -> String {
+    value.replace(['\r', '\n'], "")
+}
+
+fn build_ffmpeg_record_command(
+    url: &str,
+    filename: &str,
+    proxy: Option<String>,
+    headers: &HashMap<String, String>,
+) -> Vec<String> {
+    // User-Agent 用 -user_agent 单独传，没有指定时使用 Chrome
+    let user_agent = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("user-agent"))
+        .map(|(_, v)| strip_line_breaks(v))
+        .unwrap_or_else(|| UserAgentProfile::Chrome.user_agent());
     let analyzeduration = "20000000";
     let probesize = "10000000";
     let bufsize = "8000k";
@@ -115,6 +136,17 @@ fn build_ffmpeg_record_command(url: &str, filename: &str, proxy: Option<String>)
     if let Some(proxy) = &proxy {
         ffmpeg_command.extend_from_slice(&["-http_proxy", proxy.as_str()] as &[&str]);
     }
+    // 其它请求头，例如 Referer、Cookie，每个请求头以 \r\n 结尾
+    // 请求头来自解析器和保存的 cookie，名字不合法的丢掉，值中的换行去掉，避免插入额外的请求头
+    let headers = headers
+        .iter()
+        .filter(|(k, _)| !k.eq_ignore_ascii_case("user-agent"))
+        .filter(|(k, _)| !k.is_empty() && !k.contains(['\r', '\n', ':']))
+        .map(|(k, v)| format!("{}: {}\r\n", k, strip_line_breaks(v)))
+        .collect::<String>();
+    if !headers.is_empty() {
+        ffmpeg_command.extend_from_slice(&["-headers", headers.as_str()] as &[&str]);
+    }
     let record_command = vec![
         "-y",
         "-v",
@@ -125,7 +157,7 @@ fn build_ffmpeg_record_command(url: &str, filename: &str, proxy: Option<String>)
         "error",
         "-hide_banner",
         "-user_agent",
-        user_agent,
+        &user_agent,
         "-protocol_whitelist",
         "rtmp,crypto,file,http,https,tcp,tls,udp,rtp,httpproxy",
         "-thread_queue_size",
@@ -159,6 +191,34 @@ fn build_ffmpeg_record_command(url: &str, filename: &str, proxy: Option<String>)
     ffmpeg_command.into_iter().map(|s| s.into()).collect()
 }
 
+/// 用 ffmpeg 读取 1 秒的流，能正常读取说明流可用，用于判断直接流地址是否在直播
+pub fn probe(ffmpeg_path: &str, url: &str) -> Result<bool> {
+    let mut cmd = std::process::Command::new(ffmpeg_path);
+    // 特定于 windows 的实现，使用 CommandExt，避免出现黑窗口
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+    cmd.stdout(Stdio::null()).stderr(Stdio::null());
+    cmd.args([
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-rw_timeout",
+        "10000000",
+        "-i",
+        url,
+        "-t",
+        "1",
+        "-f",
+        "null",
+        "-",
+    ]);
+    let status = cmd.status()?;
+    Ok(status.success())
+}
+
 /// 使用 ffmpeg 转换 ts 为 mp4
 #[allow(unused)]
 pub fn convert_ts_to_mp4(ts_file: &str, delete_origin_file: bool) -> Result<()> {
@@ -256,7 +316,7 @@ mod tests {
     async fn test_record() {
         let url = "http://pull-hls-l13.douyincdn.com/stage/stream-691574246930121144_or4.m3u8?expire=1715939773&sign=f73837f8a9bac9cac894a331e8a621cf";
         let filename = "test.ts";
-        let child = record("ffmpeg", url, filename, None).unwrap();
+        let child = record("ffmpeg", url, filename, None, &HashMap::new()).unwrap();
         let output = child.wait_with_output().unwrap();
         let stdout = String::from_utf8_lossy(&output.stdout);
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -264,6 +324,48 @@ mod tests {
         println!("stderr: {}", stderr);
     }
 
+    #[test]
+    fn test_build_record_command_headers() {
+        let url = "https://example.com/live.flv";
+        let command = build_ffmpeg_record_command(url, "test.ts", None, &HashMap::new());
+        assert!(!command.contains(&"-headers".to_string()));
+
+        let headers = HashMap::from([
+            (
+                "Referer".to_string(),
+                "https://live.bilibili.com/".to_string(),
+            ),
+            ("User-Agent".to_string(), "test-agent".to_string()),
+        ]);
+        let command = build_ffmpeg_record_command(url, "test.ts", None, &headers);
+        let index = command.iter().position(|arg| arg == "-headers").unwrap();
+        assert_eq!(
+            command[index + 1],
+            "Referer: https://live.bilibili.com/\r\n"
+        );
+        // User-Agent 单独传，不放在 -headers 里
+        let index = command.iter().position(|arg| arg == "-user_agent").unwrap();
+        assert_eq!(command[index + 1], "test-agent");
+        // 请求头要放在 -i 之前才会生效
+        let input = command.iter().position(|arg| arg == "-i").unwrap();
+        assert!(command.iter().position(|arg| arg == "-headers").unwrap() < input);
+
+        // 换行不能插入额外的请求头
+        let headers = HashMap::from([
+            ("Cookie".to_string(), "a=1\r\nX-Injected: 1".to_string()),
+            ("Bad\r\nName".to_string(), "2".to_string()),
+            (
+                "User-Agent".to_string(),
+                "agent\r\nX-Injected: 1".to_string(),
+            ),
+        ]);
+        let command = build_ffmpeg_record_command(url, "test.ts", None, &headers);
+        let index = command.iter().position(|arg| arg == "-headers").unwrap();
+        assert_eq!(command[index + 1], "Cookie: a=1X-Injected: 1\r\n");
+        let index = command.iter().position(|arg| arg == "-user_agent").unwrap();
+        assert_eq!(command[index + 1], "agentX-Injected: 1");
+    }
+
     #[test]
     fn test_download_ffmpeg() {
         let ffmpeg_path = download_ffmpeg().unwrap();
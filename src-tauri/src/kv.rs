@@ -1,34 +1,576 @@
 use crate::config::config_dir;
+use crate::model::Anchor;
 use crate::model::AppConfig;
+use crate::model::BackupInfo;
+use crate::model::CustomPlatform;
+use crate::model::DbCheckReport;
 use crate::model::LiveInfo;
+use crate::model::LiveSample;
+use crate::model::PlanGroup;
+use crate::model::PlatformCredential;
+use crate::model::PlatformKind;
 use crate::model::QueryHistory;
 use crate::model::RecordingHistory;
 use crate::model::RecordingPlan;
+use crate::model::Relocation;
 use anyhow::Result;
 use chrono::Utc;
 use once_cell::sync::OnceCell;
-use redb::{Database, ReadableTable, TableDefinition, WriteTransaction};
+use redb::{
+    Database, MultimapTableDefinition, ReadTransaction, ReadableMultimapTable, ReadableTable,
+    TableDefinition, WriteTransaction,
+};
 
 static INSTANCE: OnceCell<Database> = OnceCell::new();
+// 配置和其它零散的数据，键带有前缀
 const TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("liveship");
+// 录制计划，键是 url
+const PLANS: TableDefinition<&str, &[u8]> = TableDefinition::new("plans");
+// 录制历史，键是 (url, 开始时间)
+const HISTORIES: TableDefinition<(&str, i64), &[u8]> = TableDefinition::new("histories");
+// 直播间信息，键是 url
+const LIVES: TableDefinition<&str, &[u8]> = TableDefinition::new("lives");
+// 主播，键是 url，写入直播间信息时更新
+const ANCHORS: TableDefinition<&str, &[u8]> = TableDefinition::new("anchors");
+// 录制过程中的直播间状态，键是 (url, 录制开始时间, 记录时间)
+const SAMPLES: TableDefinition<(&str, i64, i64), &[u8]> = TableDefinition::new("samples");
+// 录制历史的索引：开始时间 -> url
+const HISTORY_BY_START: MultimapTableDefinition<i64, &str> =
+    MultimapTableDefinition::new("history_by_start");
+// 录制历史的索引：主播名 -> (开始时间, url)
+const HISTORY_BY_ANCHOR: MultimapTableDefinition<&str, (i64, &str)> =
+    MultimapTableDefinition::new("history_by_anchor");
+// 录制历史的索引：平台 -> (开始时间, url)
+const HISTORY_BY_PLATFORM: MultimapTableDefinition<&str, (i64, &str)> =
+    MultimapTableDefinition::new("history_by_platform");
 
 pub fn init() -> Result<()> {
     let path = config_dir()?.join("liveship.db");
-    let db = Database::create(path)?;
+    backup::apply_pending_restore(&path)?;
+    let db = Database::create(&path)?;
+    create_tables(&db)?;
+    let mut db = migration::run(db, &path)?;
+    maintenance::compact_if_requested(&mut db)?;
+    INSTANCE.set(db).unwrap();
+    Ok(())
+}
+
+fn create_tables(db: &Database) -> Result<()> {
     let write_txn = db.begin_write()?;
     {
         // 通过这样，在没有这个表的时候自动创建表
-        let _table = write_txn.open_table(TABLE)?;
+        write_txn.open_table(TABLE)?;
+        write_txn.open_table(PLANS)?;
+        write_txn.open_table(HISTORIES)?;
+        write_txn.open_table(LIVES)?;
+        write_txn.open_table(ANCHORS)?;
+        write_txn.open_table(SAMPLES)?;
+        write_txn.open_multimap_table(HISTORY_BY_START)?;
+        write_txn.open_multimap_table(HISTORY_BY_ANCHOR)?;
+        write_txn.open_multimap_table(HISTORY_BY_PLATFORM)?;
     }
     write_txn.commit()?;
-    INSTANCE.set(db).unwrap();
     Ok(())
 }
 
+/// 测试用，读取主播或平台索引中某个键的所有值
+#[cfg(test)]
+fn index_entries(
+    db: &Database,
+    definition: MultimapTableDefinition<&'static str, (i64, &'static str)>,
+    key: &str,
+) -> Vec<(i64, String)> {
+    let read_txn = db.begin_read().unwrap();
+    let index = read_txn.open_multimap_table(definition).unwrap();
+    let values = index.get(key).unwrap();
+    values
+        .map(|v| {
+            let guard = v.unwrap();
+            let (start_time, url) = guard.value();
+            (start_time, url.to_string())
+        })
+        .collect()
+}
+
+/// 测试用，同一个进程中的测试共用临时目录中的一个数据库
+/// 返回的锁让用到数据库和配置的测试依次运行
+#[cfg(test)]
+pub fn init_for_test() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    INSTANCE.get_or_init(|| {
+        let db = Database::create(crate::utils::test_dir("kv").join("liveship.db")).unwrap();
+        create_tables(&db).unwrap();
+        db
+    });
+    guard
+}
+
 fn db() -> &'static Database {
     INSTANCE.get().unwrap()
 }
 
+/// 数据库结构的版本和迁移，迁移按版本号顺序在 init 中执行
+mod migration {
+    use super::*;
+    use anyhow::{anyhow, Context};
+    use serde_json::Value as JsonValue;
+    use std::path::Path;
+
+    const VERSION_KEY: &str = "schema_version";
+
+    type Migration = fn(&WriteTransaction) -> Result<()>;
+
+    /// 所有的迁移，版本号从 1 开始递增，执行完第 n 个迁移后数据库的版本就是 n
+    const MIGRATIONS: &[(u32, &str, Migration)] = &[
+        (1, "rewrite plans", rewrite_plans),
+        (2, "split typed tables", split_tables),
+    ];
+
+    /// 当前代码对应的数据库版本
+    pub(super) fn latest() -> u32 {
+        MIGRATIONS
+            .last()
+            .map(|(version, _, _)| *version)
+            .unwrap_or(0)
+    }
+
+    pub(super) fn version(db: &Database) -> Result<u32> {
+        let read_txn = db.begin_read()?;
+        let table = read_txn.open_table(TABLE)?;
+        let version = match table.get(VERSION_KEY)? {
+            Some(version) => u32::from_be_bytes(version.value().try_into()?),
+            None => 0,
+        };
+        Ok(version)
+    }
+
+    /// 旧的表里什么都没有，说明是刚创建的数据库，没有需要迁移的数据
+    fn is_fresh(db: &Database) -> Result<bool> {
+        let read_txn = db.begin_read()?;
+        let table = read_txn.open_table(TABLE)?;
+        Ok(table.iter()?.next().is_none())
+    }
+
+    /// 执行还没有执行过的迁移，执行前先备份数据库文件，新安装时没有数据，不需要备份
+    /// 所有迁移在同一个事务中执行，任何一个失败都不会留下修改，数据库保持原来的版本
+    pub fn run(db: Database, path: &Path) -> Result<Database> {
+        let current = version(&db)?;
+        let latest = latest();
+        if current > latest {
+            return Err(anyhow!(
+                "database version {} is newer than supported version {}",
+                current,
+                latest
+            ));
+        }
+        if current == latest {
+            return Ok(db);
+        }
+        let db = if current == 0 && is_fresh(&db)? {
+            db
+        } else {
+            // 关闭数据库再复制文件，保证备份是完整的
+            drop(db);
+            let backup = path.with_extension(format!("db.v{}.bak", current));
+            std::fs::copy(path, &backup).context("could not back up database before migration")?;
+            println!("数据库已备份到 {}", backup.display());
+            Database::create(path)?
+        };
+        let write_txn = db.begin_write()?;
+        for (version, name, migrate) in MIGRATIONS.iter().filter(|(v, _, _)| *v > current) {
+            println!("执行数据库迁移 {}：{}", version, name);
+            migrate(&write_txn)
+                .with_context(|| format!("migration {} ({}) failed", version, name))?;
+        }
+        {
+            let mut table = write_txn.open_table(TABLE)?;
+            table.insert(VERSION_KEY, latest.to_be_bytes().as_slice())?;
+        }
+        write_txn.commit()?;
+        Ok(db)
+    }
+
+    // 迁移只使用下面写死的表定义和 json 结构，以后类型和表的变化不会改变迁移对旧数据库做的事
+    mod v2 {
+        use redb::{MultimapTableDefinition, TableDefinition};
+
+        pub const PLANS: TableDefinition<&str, &[u8]> = TableDefinition::new("plans");
+        pub const HISTORIES: TableDefinition<(&str, i64), &[u8]> =
+            TableDefinition::new("histories");
+        pub const LIVES: TableDefinition<&str, &[u8]> = TableDefinition::new("lives");
+        pub const ANCHORS: TableDefinition<&str, &[u8]> = TableDefinition::new("anchors");
+        pub const HISTORY_BY_START: MultimapTableDefinition<i64, &str> =
+            MultimapTableDefinition::new("history_by_start");
+        pub const HISTORY_BY_ANCHOR: MultimapTableDefinition<&str, (i64, &str)> =
+            MultimapTableDefinition::new("history_by_anchor");
+        pub const HISTORY_BY_PLATFORM: MultimapTableDefinition<&str, (i64, &str)> =
+            MultimapTableDefinition::new("history_by_platform");
+    }
+
+    // 1: 把以前靠 serde(default) 补上的计划字段写进数据库，默认值是版本 1 时的
+    fn rewrite_plans(write_txn: &WriteTransaction) -> Result<()> {
+        let defaults = [
+            (
+                "option",
+                serde_json::json!({
+                    "useProxy": null,
+                    "autoConvertToMp4": false,
+                    "deleteOriginalFile": false
+                }),
+            ),
+            ("displayName", JsonValue::Null),
+            ("qualityPolicy", JsonValue::Null),
+            ("conditions", serde_json::json!({})),
+            ("tags", serde_json::json!([])),
+        ];
+        let mut table = write_txn.open_table(TABLE)?;
+        let mut plans = vec![];
+        for kv in table.range("plan:".."planz")? {
+            let (key, plan) = kv?;
+            let mut plan: JsonValue = serde_json::from_slice(plan.value())?;
+            let fields = plan
+                .as_object_mut()
+                .ok_or_else(|| anyhow!("invalid plan {}", key.value()))?;
+            for (name, value) in &defaults {
+                fields.entry(*name).or_insert_with(|| value.clone());
+            }
+            plans.push((key.value().to_string(), serde_json::to_vec(&plan)?));
+        }
+        for (key, plan) in plans {
+            table.insert(key.as_str(), &*plan)?;
+        }
+        Ok(())
+    }
+
+    // 2: 计划、录制历史和直播间信息从 TABLE 移到各自的表中，并建立录制历史的索引
+    fn split_tables(write_txn: &WriteTransaction) -> Result<()> {
+        let mut table = write_txn.open_table(TABLE)?;
+        let mut moved = vec![];
+        {
+            let mut plans = write_txn.open_table(v2::PLANS)?;
+            for kv in table.range("plan:".."planz")? {
+                let (key, plan) = kv?;
+                let key = key.value();
+                plans.insert(&key["plan:".len()..], plan.value())?;
+                moved.push(key.to_string());
+            }
+        }
+        let mut lives = std::collections::HashMap::new();
+        {
+            let mut live_table = write_txn.open_table(v2::LIVES)?;
+            let mut anchors = write_txn.open_table(v2::ANCHORS)?;
+            for kv in table.range("live:".."livez")? {
+                let (key, live) = kv?;
+                let url = &key.value()["live:".len()..];
+                let live: JsonValue = serde_json::from_slice(live.value())?;
+                live_table.insert(url, &*serde_json::to_vec(&live)?)?;
+                // 主播名是空的说明解析失败了，不记录主播
+                let anchor_name = live["anchorName"].as_str().unwrap_or_default();
+                if !anchor_name.is_empty() {
+                    let anchor = serde_json::json!({
+                        "url": url,
+                        "anchorName": anchor_name,
+                        "anchorAvatar": live["anchorAvatar"].as_str().unwrap_or_default(),
+                        "platformKind": live["platformKind"],
+                    });
+                    anchors.insert(url, &*serde_json::to_vec(&anchor)?)?;
+                }
+                moved.push(key.value().to_string());
+                lives.insert(url.to_string(), live);
+            }
+        }
+        let mut histories = write_txn.open_table(v2::HISTORIES)?;
+        let mut by_start = write_txn.open_multimap_table(v2::HISTORY_BY_START)?;
+        let mut by_anchor = write_txn.open_multimap_table(v2::HISTORY_BY_ANCHOR)?;
+        let mut by_platform = write_txn.open_multimap_table(v2::HISTORY_BY_PLATFORM)?;
+        for kv in table.range("history:".."historyz")? {
+            let (key, history) = kv?;
+            let mut history: JsonValue = serde_json::from_slice(history.value())?;
+            let url = history["url"].as_str().unwrap_or_default().to_string();
+            let start_time = history["startTime"]
+                .as_i64()
+                .ok_or_else(|| anyhow!("invalid history {}", key.value()))?;
+            // 旧的录制历史没有保存直播间信息，用当前的补上，索引需要主播名和平台
+            if history["liveInfo"].is_null() {
+                if let Some(live) = lives.get(&url) {
+                    history["liveInfo"] = live.clone();
+                }
+            }
+            // 平台保存到录制历史中，以后不再根据网址判断
+            // 迁移时自定义平台还没有加载，没有直播间信息的只能识别内置平台
+            if history["platformKind"].is_null() {
+                history["platformKind"] = match &history["liveInfo"]["platformKind"] {
+                    JsonValue::Null => v2_platform_kind(&url).into(),
+                    platform_kind => platform_kind.clone(),
+                };
+            }
+            let anchor = history["liveInfo"]["anchorName"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            // 自定义平台保存为 {"Custom": 平台名}，索引中使用平台名
+            let platform = match &history["platformKind"] {
+                JsonValue::Object(custom) => custom
+                    .get("Custom")
+                    .and_then(|name| name.as_str())
+                    .unwrap_or_default(),
+                platform_kind => platform_kind.as_str().unwrap_or("Unknown"),
+            }
+            .to_string();
+            histories.insert((url.as_str(), start_time), &*serde_json::to_vec(&history)?)?;
+            by_start.insert(start_time, url.as_str())?;
+            by_anchor.insert(anchor.as_str(), (start_time, url.as_str()))?;
+            by_platform.insert(platform.as_str(), (start_time, url.as_str()))?;
+            moved.push(key.value().to_string());
+        }
+        for key in moved {
+            table.remove(key.as_str())?;
+        }
+        Ok(())
+    }
+
+    // 版本 2 时内置平台的网址规则，和当时的 PlatformKind::from 一致
+    fn v2_platform_kind(url: &str) -> &'static str {
+        let url = url.to_lowercase();
+        let prefixes = [
+            ("https://live.douyin.com/", "Douyin"),
+            ("https://v.douyin.com/", "Douyin"),
+            ("https://www.tiktok.com/", "Tiktok"),
+            ("https://www.xiaohongshu.com/", "Xiaohongshu"),
+            ("https://www.huya.com/", "Huya"),
+            ("https://live.bilibili.com/", "Bilibili"),
+        ];
+        if let Some((_, platform_kind)) = prefixes.iter().find(|(p, _)| url.starts_with(p)) {
+            return platform_kind;
+        }
+        let direct = reqwest::Url::parse(url.trim()).is_ok_and(|url| {
+            matches!(url.scheme(), "rtmp" | "rtmps" | "rtsp" | "srt")
+                || (matches!(url.scheme(), "http" | "https")
+                    && [".flv", ".m3u8"]
+                        .iter()
+                        .any(|ext| url.path().ends_with(ext)))
+        });
+        if direct {
+            "Direct"
+        } else {
+            "Unknown"
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn open(dir: &Path) -> (Database, std::path::PathBuf) {
+            let path = dir.join("liveship.db");
+            let db = Database::create(&path).unwrap();
+            create_tables(&db).unwrap();
+            (db, path)
+        }
+
+        fn set_version(db: &Database, version: u32) {
+            let write_txn = db.begin_write().unwrap();
+            {
+                let mut table = write_txn.open_table(TABLE).unwrap();
+                table
+                    .insert(VERSION_KEY, version.to_be_bytes().as_slice())
+                    .unwrap();
+            }
+            write_txn.commit().unwrap();
+        }
+
+        #[test]
+        fn test_run_fresh() {
+            let dir = crate::utils::test_dir("migration-fresh");
+            let (db, path) = open(&dir);
+            let db = run(db, &path).unwrap();
+            assert_eq!(version(&db).unwrap(), latest());
+            // 新安装没有数据，不需要备份
+            assert!(!path.with_extension("db.v0.bak").exists());
+            std::fs::remove_dir_all(&dir).ok();
+        }
+
+        #[test]
+        fn test_run_existing() {
+            let dir = crate::utils::test_dir("migration-existing");
+            let (db, path) = open(&dir);
+            let write_txn = db.begin_write().unwrap();
+            {
+                let mut table = write_txn.open_table(TABLE).unwrap();
+                table.insert("config", b"{}".as_slice()).unwrap();
+            }
+            write_txn.commit().unwrap();
+
+            let db = run(db, &path).unwrap();
+            assert_eq!(version(&db).unwrap(), latest());
+            assert!(path.with_extension("db.v0.bak").exists());
+
+            // 已经是最新版本时不做任何事
+            let db = run(db, &path).unwrap();
+            assert_eq!(version(&db).unwrap(), latest());
+
+            // 比当前代码新的数据库不能打开
+            set_version(&db, latest() + 1);
+            assert!(run(db, &path).is_err());
+            std::fs::remove_dir_all(&dir).ok();
+        }
+
+        #[test]
+        fn test_split_tables() {
+            let dir = crate::utils::test_dir("migration-split");
+            let (db, path) = open(&dir);
+            let url = "https://live.douyin.com/1";
+            let plan = RecordingPlan::new_with_url(url);
+            let mut live = LiveInfo::new(url, PlatformKind::Douyin);
+            live.anchor_name = "张三".into();
+            // 旧的录制历史没有保存平台和直播间信息
+            let mut history = RecordingHistory::new(url, "/a.ts", PlatformKind::Douyin);
+            history.platform_kind = None;
+            history.status = crate::model::RecordStatus::NotRecording;
+            history.start_time = 1000;
+            history.end_time = 2000;
+            // 没有直播间信息的直接流地址，只能根据网址判断平台
+            let direct_url = "https://example.com/live.m3u8";
+            let mut direct = RecordingHistory::new(direct_url, "/b.ts", PlatformKind::Direct);
+            direct.platform_kind = None;
+            direct.start_time = 3000;
+
+            let write_txn = db.begin_write().unwrap();
+            {
+                let mut table = write_txn.open_table(TABLE).unwrap();
+                let plan_key = format!("plan:{}", url);
+                let plan_value = serde_json::to_vec(&plan).unwrap();
+                table.insert(plan_key.as_str(), &*plan_value).unwrap();
+                let live_key = format!("live:{}", url);
+                let live_value = serde_json::to_vec(&live).unwrap();
+                table.insert(live_key.as_str(), &*live_value).unwrap();
+                for history in [&history, &direct] {
+                    let key = format!("history:{}:{}", history.url, history.start_time);
+                    let value = serde_json::to_vec(history).unwrap();
+                    table.insert(key.as_str(), &*value).unwrap();
+                }
+            }
+            write_txn.commit().unwrap();
+            set_version(&db, 1);
+
+            let db = run(db, &path).unwrap();
+            assert_eq!(version(&db).unwrap(), latest());
+
+            let read_txn = db.begin_read().unwrap();
+            let table = read_txn.open_table(TABLE).unwrap();
+            for prefix in ["plan:", "live:", "history:"] {
+                let end = format!("{}\u{10FFFF}", prefix);
+                assert!(table.range(prefix..end.as_str()).unwrap().next().is_none());
+            }
+            assert!(read_txn
+                .open_table(PLANS)
+                .unwrap()
+                .get(url)
+                .unwrap()
+                .is_some());
+            assert!(read_txn
+                .open_table(LIVES)
+                .unwrap()
+                .get(url)
+                .unwrap()
+                .is_some());
+            assert!(read_txn
+                .open_table(ANCHORS)
+                .unwrap()
+                .get(url)
+                .unwrap()
+                .is_some());
+            let histories = read_txn.open_table(HISTORIES).unwrap();
+            let migrated: RecordingHistory =
+                serde_json::from_slice(histories.get((url, 1000i64)).unwrap().unwrap().value())
+                    .unwrap();
+            assert_eq!(migrated.platform_kind, Some(PlatformKind::Douyin));
+            assert_eq!(migrated.live_info.unwrap().anchor_name, "张三");
+            let migrated: RecordingHistory = serde_json::from_slice(
+                histories
+                    .get((direct_url, 3000i64))
+                    .unwrap()
+                    .unwrap()
+                    .value(),
+            )
+            .unwrap();
+            assert_eq!(migrated.platform_kind, Some(PlatformKind::Direct));
+            drop(histories);
+            drop(table);
+            drop(read_txn);
+
+            let read_txn = db.begin_read().unwrap();
+            let by_start = read_txn.open_multimap_table(HISTORY_BY_START).unwrap();
+            let urls: Vec<String> = by_start
+                .get(1000i64)
+                .unwrap()
+                .map(|v| v.unwrap().value().to_string())
+                .collect();
+            assert_eq!(urls, vec![url.to_string()]);
+            drop(by_start);
+            drop(read_txn);
+            let expected = vec![(1000, url.to_string())];
+            assert_eq!(index_entries(&db, HISTORY_BY_ANCHOR, "张三"), expected);
+            assert_eq!(index_entries(&db, HISTORY_BY_PLATFORM, "Douyin"), expected);
+            assert_eq!(
+                index_entries(&db, HISTORY_BY_PLATFORM, "Direct"),
+                vec![(3000, direct_url.to_string())]
+            );
+            std::fs::remove_dir_all(&dir).ok();
+        }
+
+        #[test]
+        fn test_rewrite_plans() {
+            let dir = crate::utils::test_dir("migration-plans");
+            let (db, path) = open(&dir);
+            // 最早版本的计划，没有后来加上的字段
+            let url = "https://live.douyin.com/1";
+            let plan = serde_json::json!({
+                "url": url,
+                "strategy": "AnchorLive",
+                "streamProtocol": "Flv",
+                "streamResolution": "原画",
+                "enabled": true,
+                "createdAt": 1,
+                "updatedAt": 0,
+                "liveInfo": null
+            });
+            let write_txn = db.begin_write().unwrap();
+            {
+                let mut table = write_txn.open_table(TABLE).unwrap();
+                let key = format!("plan:{}", url);
+                let value = serde_json::to_vec(&plan).unwrap();
+                table.insert(key.as_str(), &*value).unwrap();
+            }
+            write_txn.commit().unwrap();
+
+            let db = run(db, &path).unwrap();
+            let read_txn = db.begin_read().unwrap();
+            let plans = read_txn.open_table(PLANS).unwrap();
+            let migrated = plans.get(url).unwrap().unwrap();
+            let migrated: JsonValue = serde_json::from_slice(migrated.value()).unwrap();
+            assert_eq!(migrated["option"]["autoConvertToMp4"], false);
+            assert_eq!(migrated["tags"], serde_json::json!([]));
+            assert!(migrated.get("qualityPolicy").is_some());
+            assert!(serde_json::from_value::<RecordingPlan>(migrated).is_ok());
+            std::fs::remove_dir_all(&dir).ok();
+        }
+
+        #[test]
+        fn test_run_from_version() {
+            let dir = crate::utils::test_dir("migration-version");
+            let (db, path) = open(&dir);
+            set_version(&db, 1);
+            let db = run(db, &path).unwrap();
+            assert_eq!(version(&db).unwrap(), latest());
+            // 从哪个版本开始迁移，备份文件名就带哪个版本号
+            assert!(path.with_extension("db.v1.bak").exists());
+            std::fs::remove_dir_all(&dir).ok();
+        }
+    }
+}
+
 pub mod config {
     use super::*;
 
@@ -84,12 +626,25 @@ pub mod plan {
 
     /// 添加一个录制计划
     pub fn add(plan: &RecordingPlan) -> Result<()> {
-        let key = format!("plan:{}", plan.url);
-        let plan = serde_json::to_vec(plan)?;
+        let plan_vec = serde_json::to_vec(plan)?;
         let write_txn = db().begin_write()?;
         {
-            let mut table = write_txn.open_table(TABLE)?;
-            table.insert(key.as_str(), &*plan)?;
+            let mut table = write_txn.open_table(PLANS)?;
+            table.insert(plan.url.as_str(), &*plan_vec)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// 在一个事务中添加多个录制计划，要么全部保存，要么都不保存
+    pub fn add_all(plans: &[RecordingPlan]) -> Result<()> {
+        let write_txn = db().begin_write()?;
+        {
+            let mut table = write_txn.open_table(PLANS)?;
+            for plan in plans {
+                let plan_vec = serde_json::to_vec(plan)?;
+                table.insert(plan.url.as_str(), &*plan_vec)?;
+            }
         }
         write_txn.commit()?;
         Ok(())
@@ -97,16 +652,27 @@ pub mod plan {
 
     /// 获取所有录制计划
     pub fn get_all() -> Result<Vec<RecordingPlan>> {
+        load(|_| true)
+    }
+
+    // 读取满足条件的计划，直播间信息在同一个事务中读取
+    fn load(filter: impl Fn(&RecordingPlan) -> bool) -> Result<Vec<RecordingPlan>> {
         let read_txn = db().begin_read()?;
-        let table = read_txn.open_table(TABLE)?;
-        let iter = table.range("plan:".."planz")?;
+        let table = read_txn.open_table(PLANS)?;
+        let lives = read_txn.open_table(LIVES)?;
         let mut plans = Vec::new();
-        for kv in iter {
+        for kv in table.iter()? {
             let (_, plan) = kv?;
-            let mut plan: RecordingPlan = serde_json::from_slice(&plan.value())?;
+            let mut plan: RecordingPlan = serde_json::from_slice(plan.value())?;
+            if !filter(&plan) {
+                continue;
+            }
             // 获取直播间信息
             if plan.live_info.is_none() {
-                plan.live_info = super::live::get(&plan.url)?;
+                plan.live_info = match lives.get(plan.url.as_str())? {
+                    Some(live) => Some(serde_json::from_slice(live.value())?),
+                    None => None,
+                };
             }
             plans.push(plan);
         }
@@ -117,45 +683,44 @@ pub mod plan {
     /// 获取一个录制计划
     pub fn get(url: String) -> Result<Option<RecordingPlan>> {
         let read_txn = db().begin_read()?;
-        let table = read_txn.open_table(TABLE)?;
-        let result = table.get(format!("plan:{}", url).as_str())?;
+        let table = read_txn.open_table(PLANS)?;
+        let result = table.get(url.as_str())?;
         match result {
             Some(plan) => {
-                let plan: RecordingPlan = serde_json::from_slice(&plan.value())?;
+                let plan: RecordingPlan = serde_json::from_slice(plan.value())?;
                 Ok(Some(plan))
             }
             None => Ok(None),
         }
     }
 
+    /// 获取一个录制计划，并用所属分组的默认设置补全，用于开始录制
+    pub fn get_with_defaults(url: String) -> Result<Option<RecordingPlan>> {
+        let Some(mut plan) = get(url)? else {
+            return Ok(None);
+        };
+        if !plan.tags.is_empty() {
+            plan.inherit(&super::group::get_all()?);
+        }
+        Ok(Some(plan))
+    }
+
+    /// 获取有某个标签的录制计划
+    pub fn get_by_tag(tag: &str) -> Result<Vec<RecordingPlan>> {
+        load(|plan| plan.tags.iter().any(|t| t == tag))
+    }
+
     /// 获取所有启用的录制计划
     pub fn get_enabled() -> Result<Vec<RecordingPlan>> {
-        let read_txn = db().begin_read()?;
-        let table = read_txn.open_table(TABLE)?;
-        let iter = table.range("plan:".."planz")?;
-        let mut plans = Vec::new();
-        for kv in iter {
-            let (_, plan) = kv?;
-            let mut plan: RecordingPlan = serde_json::from_slice(&plan.value())?;
-            if plan.enabled {
-                // 获取直播间信息
-                if plan.live_info.is_none() {
-                    plan.live_info = super::live::get(&plan.url)?;
-                }
-                plans.push(plan);
-            }
-        }
-        sort(&mut plans);
-        Ok(plans)
+        load(|plan| plan.enabled)
     }
 
     /// 删除一个录制计划，即使不存在也不会报错
     pub fn delete(url: &str) -> Result<()> {
-        let key = format!("plan:{}", url);
         let write_txn = db().begin_write()?;
         {
-            let mut table = write_txn.open_table(TABLE)?;
-            table.remove(key.as_str())?;
+            let mut table = write_txn.open_table(PLANS)?;
+            table.remove(url)?;
         }
         write_txn.commit()?;
         Ok(())
@@ -163,27 +728,62 @@ pub mod plan {
 
     /// 更新录制计划的状态
     pub fn update_status(url: &str, enabled: bool) -> Result<()> {
-        let key = format!("plan:{}", url);
+        update(url, |plan| {
+            plan.enabled = enabled;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// 在一个事务中读取并修改录制计划，update 返回错误时不会保存
+    pub fn update(
+        url: &str,
+        update: impl FnOnce(&mut RecordingPlan) -> Result<()>,
+    ) -> Result<RecordingPlan> {
         let write_txn = db().begin_write()?;
-        {
-            let mut table = write_txn.open_table(TABLE)?;
-            let plan_vec;
-            if let Some(result) = table.get(key.as_str())? {
-                let mut plan: RecordingPlan = serde_json::from_slice(&result.value())?;
-                plan.enabled = enabled;
-                let plan = serde_json::to_vec(&plan)?;
-                plan_vec = plan;
-            } else {
-                return Err(anyhow::anyhow!("plan not found"));
+        let plan = {
+            let mut table = write_txn.open_table(PLANS)?;
+            let mut plan: RecordingPlan = match table.get(url)? {
+                Some(result) => serde_json::from_slice(result.value())?,
+                None => return Err(anyhow::anyhow!("plan not found")),
+            };
+            update(&mut plan)?;
+            table.insert(url, &*serde_json::to_vec(&plan)?)?;
+            plan
+        };
+        write_txn.commit()?;
+        Ok(plan)
+    }
+
+    /// 在一个事务中修改分组中的所有计划，任何一个计划 update 返回错误时都不会保存
+    pub fn update_by_tag(
+        tag: &str,
+        update: impl Fn(&mut RecordingPlan) -> Result<()>,
+    ) -> Result<Vec<RecordingPlan>> {
+        let write_txn = db().begin_write()?;
+        let mut plans = {
+            let mut table = write_txn.open_table(PLANS)?;
+            let mut plans = vec![];
+            for kv in table.iter()? {
+                let (_, plan) = kv?;
+                let plan: RecordingPlan = serde_json::from_slice(plan.value())?;
+                if plan.tags.iter().any(|t| t == tag) {
+                    plans.push(plan);
+                }
             }
-            table.insert(key.as_str(), &*plan_vec)?;
-        }
+            for plan in &mut plans {
+                update(plan).map_err(|e| anyhow::anyhow!("{}: {}", plan.url, e))?;
+                table.insert(plan.url.as_str(), &*serde_json::to_vec(plan)?)?;
+            }
+            plans
+        };
         write_txn.commit()?;
-        Ok(())
+        sort(&mut plans);
+        Ok(plans)
     }
 
     /// 对录制计划进行排序，按照 created_at 降序
-    fn sort(plans: &mut Vec<RecordingPlan>) {
+    fn sort(plans: &mut [RecordingPlan]) {
         plans.sort_by(|a, b| b.created_at.cmp(&a.created_at));
     }
 }
@@ -230,128 +830,386 @@ pub mod recording {
 
 pub mod history {
     use super::*;
+    use std::collections::HashMap;
+    use std::ops::Range;
 
     /// 增加一条录制历史
     pub fn add(history: &RecordingHistory) -> Result<()> {
         let write_txn = db().begin_write()?;
         // 先增加正在录制中的标记，如果这个 url 已经存在，会直接报错
         recording::add(&write_txn, &history.url, history.start_time)?;
-        {
-            let mut table = write_txn.open_table(TABLE)?;
-            // 然后增加历史记录
-            let key = format!("history:{}:{}", history.url, history.start_time);
-            let history = serde_json::to_vec(history)?;
-            table.insert(key.as_str(), &*history)?;
-        }
+        // 然后增加历史记录
+        insert(&write_txn, history)?;
         write_txn.commit()?;
         Ok(())
     }
 
-    /// 获取所有录制历史
-    pub fn get_all() -> Result<Vec<RecordingHistory>> {
+    /// 写入一条录制历史和它的索引
+    pub(super) fn insert(write_txn: &WriteTransaction, history: &RecordingHistory) -> Result<()> {
+        let mut table = write_txn.open_table(HISTORIES)?;
+        let key = (history.url.as_str(), history.start_time);
+        table.insert(key, &*serde_json::to_vec(history)?)?;
+        index(write_txn, history)
+    }
+
+    /// 为一条录制历史写入索引
+    pub(super) fn index(write_txn: &WriteTransaction, history: &RecordingHistory) -> Result<()> {
+        let (anchor, platform) = index_keys(history);
+        let value = (history.start_time, history.url.as_str());
+        write_txn
+            .open_multimap_table(HISTORY_BY_START)?
+            .insert(history.start_time, history.url.as_str())?;
+        write_txn
+            .open_multimap_table(HISTORY_BY_ANCHOR)?
+            .insert(anchor.as_str(), value)?;
+        write_txn
+            .open_multimap_table(HISTORY_BY_PLATFORM)?
+            .insert(platform.as_str(), value)?;
+        Ok(())
+    }
+
+    // 索引用的主播名和平台，取录制开始时保存的平台和直播间信息，不根据网址重新判断平台
+    pub(crate) fn index_keys(history: &RecordingHistory) -> (String, String) {
+        let anchor = history
+            .live_info
+            .as_ref()
+            .map(|live_info| live_info.anchor_name.clone())
+            .unwrap_or_default();
+        let platform = history
+            .platform_kind
+            .clone()
+            .or_else(|| history.live_info.as_ref().map(|i| i.platform_kind.clone()))
+            .unwrap_or(PlatformKind::Unknown);
+        (anchor, platform.to_string())
+    }
+
+    // 在同一个事务中读取多条录制历史，同一个直播间的直播信息只读取一次
+    fn load(
+        read_txn: &ReadTransaction,
+        keys: impl IntoIterator<Item = (String, i64)>,
+    ) -> Result<Vec<RecordingHistory>> {
+        let table = read_txn.open_table(HISTORIES)?;
+        let lives = read_txn.open_table(LIVES)?;
+        let mut live_cache: HashMap<String, Option<LiveInfo>> = HashMap::new();
         let mut histories = Vec::new();
-        let read_txn = db().begin_read()?;
-        let table = read_txn.open_table(TABLE)?;
-        let iter = table.range("history:".."historyz")?;
-        for kv in iter {
-            let (_, history) = kv?;
-            let mut history: RecordingHistory = serde_json::from_slice(&history.value())?;
+        for (url, start_time) in keys {
+            let Some(history) = table.get((url.as_str(), start_time))? else {
+                continue;
+            };
+            let mut history: RecordingHistory = serde_json::from_slice(history.value())?;
             // 获取直播间信息
             if history.live_info.is_none() {
-                history.live_info = super::live::get(&history.url).map_err(|e| {
-                    eprintln!("get live info error: {:?}", e);
-                    e
-                })?;
+                if !live_cache.contains_key(&url) {
+                    let live_info = match lives.get(url.as_str())? {
+                        Some(live) => Some(serde_json::from_slice(live.value())?),
+                        None => None,
+                    };
+                    live_cache.insert(url.clone(), live_info);
+                }
+                history.live_info = live_cache[&url].clone();
             }
             histories.push(history);
         }
+        Ok(histories)
+    }
+
+    /// 获取所有录制历史
+    pub fn get_all() -> Result<Vec<RecordingHistory>> {
+        get_by_time(i64::MIN..i64::MAX)
+    }
+
+    /// 获取开始时间在范围内的录制历史，按开始时间降序
+    pub fn get_by_time(range: Range<i64>) -> Result<Vec<RecordingHistory>> {
+        let read_txn = db().begin_read()?;
+        let index = read_txn.open_multimap_table(HISTORY_BY_START)?;
+        let mut keys = vec![];
+        for kv in index.range(range)? {
+            let (start_time, urls) = kv?;
+            for url in urls {
+                keys.push((url?.value().to_string(), start_time.value()));
+            }
+        }
+        let mut histories = load(&read_txn, keys)?;
+        sort(&mut histories);
+        Ok(histories)
+    }
+
+    /// 获取某个主播的录制历史，按开始时间降序
+    pub fn get_by_anchor(anchor_name: &str) -> Result<Vec<RecordingHistory>> {
+        get_by_index(HISTORY_BY_ANCHOR, anchor_name)
+    }
+
+    /// 获取某个平台的录制历史，按开始时间降序
+    pub fn get_by_platform(platform_kind: &PlatformKind) -> Result<Vec<RecordingHistory>> {
+        get_by_index(HISTORY_BY_PLATFORM, &platform_kind.to_string())
+    }
+
+    fn get_by_index(
+        definition: MultimapTableDefinition<'static, &'static str, (i64, &'static str)>,
+        key: &str,
+    ) -> Result<Vec<RecordingHistory>> {
+        let read_txn = db().begin_read()?;
+        let index = read_txn.open_multimap_table(definition)?;
+        let mut keys = vec![];
+        for value in index.get(key)? {
+            let value = value?;
+            let (start_time, url) = value.value();
+            keys.push((url.to_string(), start_time));
+        }
+        let mut histories = load(&read_txn, keys)?;
         sort(&mut histories);
         Ok(histories)
     }
 
     /// 获取一条录制历史
     pub fn get(url: &str, start_time: i64) -> Result<RecordingHistory> {
-        let key = format!("history:{}:{}", url, start_time);
         let read_txn = db().begin_read()?;
-        let table = read_txn.open_table(TABLE)?;
-        let result = table.get(key.as_str())?;
+        let table = read_txn.open_table(HISTORIES)?;
+        let result = table.get((url, start_time))?;
         match result {
             Some(history) => {
-                let history: RecordingHistory = serde_json::from_slice(&history.value())?;
+                let history: RecordingHistory = serde_json::from_slice(history.value())?;
                 Ok(history)
             }
             None => Err(anyhow::anyhow!("history not found")),
         }
     }
 
-    /// 删除一条录制历史
-    pub fn delete(url: &str, start_time: i64, delete_file: bool) -> Result<()> {
-        if delete_file {
-            let history = get(url, start_time)?;
-            std::fs::remove_file(history.path)?;
+    /// 获取一个 url 正在录制中的那条历史，没有在录制时返回 None
+    pub fn get_recording(url: &str) -> Result<Option<RecordingHistory>> {
+        let read_txn = db().begin_read()?;
+        let table = read_txn.open_table(TABLE)?;
+        let Some(start_time) = table.get(format!("recording:{}", url).as_str())? else {
+            return Ok(None);
+        };
+        let start_time = i64::from_be_bytes(start_time.value().try_into()?);
+        let histories = read_txn.open_table(HISTORIES)?;
+        match histories.get((url, start_time))? {
+            Some(history) => Ok(Some(serde_json::from_slice(history.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 获取一个直播间所有录制的开始时间，从早到晚排列
+    pub fn get_start_times(url: &str) -> Result<Vec<i64>> {
+        let read_txn = db().begin_read()?;
+        let table = read_txn.open_table(HISTORIES)?;
+        let mut start_times = vec![];
+        for kv in table.range((url, i64::MIN)..=(url, i64::MAX))? {
+            let (key, _) = kv?;
+            start_times.push(key.value().1);
         }
-        let key = format!("history:{}:{}", url, start_time);
+        Ok(start_times)
+    }
+
+    /// 在同一个事务中修改一条录制历史，只能修改不影响索引的字段
+    pub fn update(
+        url: &str,
+        start_time: i64,
+        f: impl FnOnce(&mut RecordingHistory),
+    ) -> Result<RecordingHistory> {
+        let write_txn = db().begin_write()?;
+        let history = {
+            let mut table = write_txn.open_table(HISTORIES)?;
+            let mut history: RecordingHistory = match table.get((url, start_time))? {
+                Some(history) => serde_json::from_slice(history.value())?,
+                None => return Err(anyhow::anyhow!("history not found")),
+            };
+            f(&mut history);
+            table.insert((url, start_time), &*serde_json::to_vec(&history)?)?;
+            history
+        };
+        write_txn.commit()?;
+        Ok(history)
+    }
+
+    /// 在一个事务中写回扫描得到的文件路径和指纹
+    /// 按数据库中现在的记录修改，读取时补上的其它字段不写回，索引不受影响
+    pub fn relink(histories: &[RecordingHistory]) -> Result<()> {
         let write_txn = db().begin_write()?;
         {
-            let mut table = write_txn.open_table(TABLE)?;
-            table.remove(key.as_str())?;
+            let mut table = write_txn.open_table(HISTORIES)?;
+            for history in histories {
+                let key = (history.url.as_str(), history.start_time);
+                let current = table.get(key)?.map(|v| v.value().to_vec());
+                // 扫描期间被删除的不再写回
+                let Some(current) = current else {
+                    continue;
+                };
+                let mut current: RecordingHistory = serde_json::from_slice(&current)?;
+                current.path = history.path.clone();
+                current.fingerprint = history.fingerprint.clone();
+                table.insert(key, &*serde_json::to_vec(&current)?)?;
+            }
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// 在一个事务中导入已经结束的录制历史，不增加正在录制的标记
+    pub fn import(histories: &[RecordingHistory]) -> Result<()> {
+        let write_txn = db().begin_write()?;
+        for history in histories {
+            insert(&write_txn, history)?;
         }
         write_txn.commit()?;
         Ok(())
     }
 
+    /// 删除一条录制历史，不处理录制文件
+    pub fn delete(url: &str, start_time: i64) -> Result<()> {
+        let history = get(url, start_time)?;
+        let write_txn = db().begin_write()?;
+        remove(&write_txn, &history)?;
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// 删除一条录制历史，连同它的索引和录制过程中的记录
+    pub(super) fn remove(write_txn: &WriteTransaction, history: &RecordingHistory) -> Result<()> {
+        let (url, start_time) = (history.url.as_str(), history.start_time);
+        let mut table = write_txn.open_table(HISTORIES)?;
+        table.remove((url, start_time))?;
+        let (anchor, platform) = index_keys(history);
+        let value = (start_time, url);
+        write_txn
+            .open_multimap_table(HISTORY_BY_START)?
+            .remove(start_time, url)?;
+        write_txn
+            .open_multimap_table(HISTORY_BY_ANCHOR)?
+            .remove(anchor.as_str(), value)?;
+        write_txn
+            .open_multimap_table(HISTORY_BY_PLATFORM)?
+            .remove(platform.as_str(), value)?;
+        super::sample::delete(write_txn, url, start_time)?;
+        Ok(())
+    }
+
     /// 把一条录制历史的状态设置为终止，将更新 end_time 和 status，同时将正在录制中的标记删除
-    pub fn end(url: &str) -> Result<()> {
+    /// 返回终止后的录制历史
+    pub fn end(url: &str) -> Result<RecordingHistory> {
         let write_txn = db().begin_write()?;
         let start_time = recording::delete(&write_txn, url)?;
-        let key = format!("history:{}:{}", url, start_time);
-        {
-            let mut table = write_txn.open_table(TABLE)?;
+        let history = {
+            let mut table = write_txn.open_table(HISTORIES)?;
             let history_vec;
-            if let Some(history) = table.get(key.as_str())? {
-                let mut history: RecordingHistory = serde_json::from_slice(&history.value())?;
+            let history = if let Some(history) = table.get((url, start_time))? {
+                let mut history: RecordingHistory = serde_json::from_slice(history.value())?;
                 history.end_time = Utc::now().timestamp_millis();
                 history.status = crate::model::RecordStatus::NotRecording;
-                let history = serde_json::to_vec(&history)?;
-                history_vec = history;
+                history_vec = serde_json::to_vec(&history)?;
+                history
             } else {
                 return Err(anyhow::anyhow!("history not found"));
             };
-            table.insert(key.as_str(), &*history_vec)?;
-        }
+            table.insert((url, start_time), &*history_vec)?;
+            history
+        };
         write_txn.commit()?;
-        Ok(())
+        Ok(history)
     }
 
     /// 排序，按照 start_time 降序
-    fn sort(histories: &mut Vec<RecordingHistory>) {
+    fn sort(histories: &mut [RecordingHistory]) {
         histories.sort_by(|a, b| b.start_time.cmp(&a.start_time));
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_index_keys() {
+            let url = "https://live.douyin.com/1";
+            let mut history = RecordingHistory::new(url, "", PlatformKind::Douyin);
+            assert_eq!(index_keys(&history), ("".into(), "Douyin".into()));
+            let mut live = LiveInfo::new(url, PlatformKind::Douyin);
+            live.anchor_name = "张三".into();
+            history.live_info = Some(live);
+            assert_eq!(index_keys(&history), ("张三".into(), "Douyin".into()));
+            // 使用保存的平台，不根据网址重新判断
+            history.platform_kind = Some(PlatformKind::Bilibili);
+            assert_eq!(index_keys(&history).1, "Bilibili");
+            // 没有保存平台的旧数据使用直播间信息中的平台
+            history.platform_kind = None;
+            assert_eq!(index_keys(&history).1, "Douyin");
+            history.live_info = None;
+            assert_eq!(index_keys(&history), ("".into(), "Unknown".into()));
+        }
+
+        #[test]
+        fn test_index() {
+            let dir = crate::utils::test_dir("history-index");
+            let db = Database::create(dir.join("liveship.db")).unwrap();
+            create_tables(&db).unwrap();
+            let url = "https://live.douyin.com/1";
+            let mut history = RecordingHistory::new(url, "/a.ts", PlatformKind::Douyin);
+            history.start_time = 1000;
+            let mut live = LiveInfo::new(url, PlatformKind::Douyin);
+            live.anchor_name = "张三".into();
+            history.live_info = Some(live);
+
+            let write_txn = db.begin_write().unwrap();
+            insert(&write_txn, &history).unwrap();
+            write_txn.commit().unwrap();
+            let by_start = |db: &Database| -> Vec<String> {
+                let read_txn = db.begin_read().unwrap();
+                let index = read_txn.open_multimap_table(HISTORY_BY_START).unwrap();
+                let urls = index.get(1000i64).unwrap();
+                urls.map(|v| v.unwrap().value().to_string()).collect()
+            };
+            let expected = vec![(1000, url.to_string())];
+            assert_eq!(by_start(&db), vec![url.to_string()]);
+            assert_eq!(index_entries(&db, HISTORY_BY_ANCHOR, "张三"), expected);
+            assert_eq!(index_entries(&db, HISTORY_BY_PLATFORM, "Douyin"), expected);
+
+            // 删除录制历史时索引也一起删除
+            let write_txn = db.begin_write().unwrap();
+            remove(&write_txn, &history).unwrap();
+            write_txn.commit().unwrap();
+            assert!(by_start(&db).is_empty());
+            assert!(index_entries(&db, HISTORY_BY_ANCHOR, "张三").is_empty());
+            assert!(index_entries(&db, HISTORY_BY_PLATFORM, "Douyin").is_empty());
+            std::fs::remove_dir_all(&dir).ok();
+        }
+    }
 }
 
 pub mod live {
 
     use super::*;
 
-    /// 添加一条直播信息
+    /// 添加一条直播信息，同时更新主播表
     pub fn add(live: &LiveInfo) -> Result<()> {
         let write_txn = db().begin_write()?;
-        {
-            let mut table = write_txn.open_table(TABLE)?;
-            let key = format!("live:{}", live.url);
-            let live = serde_json::to_vec(live)?;
-            table.insert(key.as_str(), &*live)?;
-        }
+        insert(&write_txn, live)?;
         write_txn.commit()?;
         Ok(())
     }
 
+    pub(super) fn insert(write_txn: &WriteTransaction, live: &LiveInfo) -> Result<()> {
+        let mut table = write_txn.open_table(LIVES)?;
+        table.insert(live.url.as_str(), &*serde_json::to_vec(live)?)?;
+        // 解析失败时主播名可能是空的，不覆盖已有的主播信息
+        if !live.anchor_name.is_empty() {
+            let anchor = Anchor {
+                url: live.url.clone(),
+                anchor_name: live.anchor_name.clone(),
+                anchor_avatar: live.anchor_avatar.clone(),
+                platform_kind: live.platform_kind.clone(),
+            };
+            let mut anchors = write_txn.open_table(ANCHORS)?;
+            anchors.insert(live.url.as_str(), &*serde_json::to_vec(&anchor)?)?;
+        }
+        Ok(())
+    }
+
     /// 删除一条直播信息
     pub fn delete(url: &str) -> Result<()> {
         let write_txn = db().begin_write()?;
         {
-            let mut table = write_txn.open_table(TABLE)?;
-            table.remove(format!("live:{}", url).as_str())?;
+            let mut table = write_txn.open_table(LIVES)?;
+            table.remove(url)?;
         }
         write_txn.commit()?;
         Ok(())
@@ -361,11 +1219,10 @@ pub mod live {
     pub fn get_all() -> Result<Vec<LiveInfo>> {
         let mut lives = Vec::new();
         let read_txn = db().begin_read()?;
-        let table = read_txn.open_table(TABLE)?;
-        let iter = table.range("live:".."livez")?;
-        for kv in iter {
+        let table = read_txn.open_table(LIVES)?;
+        for kv in table.iter()? {
             let (_, live) = kv?;
-            let live: LiveInfo = serde_json::from_slice(&live.value())?;
+            let live: LiveInfo = serde_json::from_slice(live.value())?;
             lives.push(live);
         }
         Ok(lives)
@@ -374,11 +1231,11 @@ pub mod live {
     /// 获取一条直播信息
     pub fn get(url: &str) -> Result<Option<LiveInfo>> {
         let read_txn = db().begin_read()?;
-        let table = read_txn.open_table(TABLE)?;
-        let value = table.get(format!("live:{}", url).as_str())?;
+        let table = read_txn.open_table(LIVES)?;
+        let value = table.get(url)?;
         match value {
             Some(value) => {
-                let live: LiveInfo = serde_json::from_slice(&value.value())?;
+                let live: LiveInfo = serde_json::from_slice(value.value())?;
                 Ok(Some(live))
             }
             None => Ok(None),
@@ -386,6 +1243,73 @@ pub mod live {
     }
 }
 
+/// 录制过程中记录的直播间状态，属于某一条录制历史
+pub mod sample {
+    use super::*;
+
+    /// 为正在录制的历史增加一条记录
+    pub fn add(url: &str, start_time: i64, sample: &LiveSample) -> Result<()> {
+        let write_txn = db().begin_write()?;
+        {
+            let mut table = write_txn.open_table(SAMPLES)?;
+            table.insert(
+                (url, start_time, sample.time),
+                &*serde_json::to_vec(sample)?,
+            )?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// 获取一条录制历史的所有记录，按时间排列
+    pub fn get(url: &str, start_time: i64) -> Result<Vec<LiveSample>> {
+        let read_txn = db().begin_read()?;
+        let table = read_txn.open_table(SAMPLES)?;
+        let mut samples = vec![];
+        for kv in table.range((url, start_time, i64::MIN)..=(url, start_time, i64::MAX))? {
+            let (_, sample) = kv?;
+            samples.push(serde_json::from_slice(sample.value())?);
+        }
+        Ok(samples)
+    }
+
+    /// 删除一条录制历史的所有记录
+    pub(super) fn delete(write_txn: &WriteTransaction, url: &str, start_time: i64) -> Result<()> {
+        let mut table = write_txn.open_table(SAMPLES)?;
+        table.retain_in(
+            (url, start_time, i64::MIN)..=(url, start_time, i64::MAX),
+            |_, _| false,
+        )?;
+        Ok(())
+    }
+}
+
+pub mod anchor {
+    use super::*;
+
+    /// 获取所有主播
+    pub fn get_all() -> Result<Vec<Anchor>> {
+        let mut anchors = Vec::new();
+        let read_txn = db().begin_read()?;
+        let table = read_txn.open_table(ANCHORS)?;
+        for kv in table.iter()? {
+            let (_, anchor) = kv?;
+            anchors.push(serde_json::from_slice(anchor.value())?);
+        }
+        Ok(anchors)
+    }
+
+    /// 获取一个直播间的主播
+    pub fn get(url: &str) -> Result<Option<Anchor>> {
+        let read_txn = db().begin_read()?;
+        let table = read_txn.open_table(ANCHORS)?;
+        match table.get(url)? {
+            Some(anchor) => Ok(Some(serde_json::from_slice(anchor.value())?)),
+            None => Ok(None),
+        }
+    }
+}
+
 pub mod query_history {
     use std::collections::VecDeque;
 
@@ -437,9 +1361,809 @@ pub mod query_history {
     }
 }
 
+pub mod custom_platform {
+    use super::*;
+
+    /// 添加或覆盖一个自定义平台定义
+    pub fn add(platform: &CustomPlatform) -> Result<()> {
+        let write_txn = db().begin_write()?;
+        {
+            let mut table = write_txn.open_table(TABLE)?;
+            let key = format!("custom_platform:{}", platform.name);
+            let platform = serde_json::to_vec(platform)?;
+            table.insert(key.as_str(), &*platform)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// 获取所有保存在数据库中的自定义平台定义
+    pub fn get_all() -> Result<Vec<CustomPlatform>> {
+        let mut platforms = Vec::new();
+        let read_txn = db().begin_read()?;
+        let table = read_txn.open_table(TABLE)?;
+        let iter = table.range("custom_platform:".."custom_platformz")?;
+        for kv in iter {
+            let (_, platform) = kv?;
+            let platform: CustomPlatform = serde_json::from_slice(&platform.value())?;
+            platforms.push(platform);
+        }
+        Ok(platforms)
+    }
+
+    /// 删除一个自定义平台定义
+    pub fn delete(name: &str) -> Result<()> {
+        let write_txn = db().begin_write()?;
+        {
+            let mut table = write_txn.open_table(TABLE)?;
+            table.remove(format!("custom_platform:{}", name).as_str())?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+}
+
+/// 只保存凭据的元信息，cookie 在系统钥匙串里
+pub mod credential {
+    use super::*;
+
+    /// 添加或更新一条凭据信息
+    pub fn set(credential: &PlatformCredential) -> Result<()> {
+        let write_txn = db().begin_write()?;
+        {
+            let mut table = write_txn.open_table(TABLE)?;
+            let key = key(&credential.platform_kind, &credential.host);
+            let credential = serde_json::to_vec(credential)?;
+            table.insert(key.as_str(), &*credential)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// 外部解析器的凭据按域名区分，键里带上域名，其它平台只有一条
+    fn key(platform_kind: &PlatformKind, host: &str) -> String {
+        if host.is_empty() {
+            format!("credential:{}", platform_kind)
+        } else {
+            format!("credential:{}:{}", platform_kind, host)
+        }
+    }
+
+    /// 获取一个平台的凭据信息，host 只对外部解析器有效，其它平台传空字符串
+    pub fn get(platform_kind: &PlatformKind, host: &str) -> Result<Option<PlatformCredential>> {
+        let read_txn = db().begin_read()?;
+        let table = read_txn.open_table(TABLE)?;
+        let value = table.get(key(platform_kind, host).as_str())?;
+        match value {
+            Some(value) => Ok(Some(serde_json::from_slice(&value.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 获取所有平台的凭据信息
+    pub fn get_all() -> Result<Vec<PlatformCredential>> {
+        let mut credentials = Vec::new();
+        let read_txn = db().begin_read()?;
+        let table = read_txn.open_table(TABLE)?;
+        let iter = table.range("credential:".."credentialz")?;
+        for kv in iter {
+            let (_, credential) = kv?;
+            let credential: PlatformCredential = serde_json::from_slice(&credential.value())?;
+            credentials.push(credential);
+        }
+        Ok(credentials)
+    }
+
+    /// 删除一个平台的凭据信息
+    pub fn delete(platform_kind: &PlatformKind, host: &str) -> Result<()> {
+        let write_txn = db().begin_write()?;
+        {
+            let mut table = write_txn.open_table(TABLE)?;
+            table.remove(key(platform_kind, host).as_str())?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+}
+
+pub mod group {
+    use super::*;
+
+    /// 添加或更新一个分组的默认设置
+    pub fn set(group: &PlanGroup) -> Result<()> {
+        let write_txn = db().begin_write()?;
+        {
+            let mut table = write_txn.open_table(TABLE)?;
+            let key = format!("group:{}", group.name);
+            let group = serde_json::to_vec(group)?;
+            table.insert(key.as_str(), &*group)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// 获取所有分组的默认设置
+    pub fn get_all() -> Result<Vec<PlanGroup>> {
+        let mut groups = Vec::new();
+        let read_txn = db().begin_read()?;
+        let table = read_txn.open_table(TABLE)?;
+        let iter = table.range("group:".."groupz")?;
+        for kv in iter {
+            let (_, group) = kv?;
+            let group: PlanGroup = serde_json::from_slice(&group.value())?;
+            groups.push(group);
+        }
+        Ok(groups)
+    }
+
+    /// 删除一个分组的默认设置，计划上的标签不受影响
+    pub fn delete(name: &str) -> Result<()> {
+        let write_txn = db().begin_write()?;
+        {
+            let mut table = write_txn.open_table(TABLE)?;
+            table.remove(format!("group:{}", name).as_str())?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+}
+
+/// 正在进行的录制文件搬移任务，同一时间只有一个
+pub mod relocation {
+    use super::*;
+
+    const KEY: &str = "relocation";
+
+    pub fn get() -> Result<Option<Relocation>> {
+        let read_txn = db().begin_read()?;
+        let table = read_txn.open_table(TABLE)?;
+        match table.get(KEY)? {
+            Some(relocation) => Ok(Some(serde_json::from_slice(relocation.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set(relocation: &Relocation) -> Result<()> {
+        let write_txn = db().begin_write()?;
+        {
+            let mut table = write_txn.open_table(TABLE)?;
+            table.insert(KEY, &*serde_json::to_vec(relocation)?)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// 文件搬完后，在一个事务中写回录制历史的新路径和配置中的保存路径，同时删除任务
+    /// 搬移期间录制历史和配置可能被修改，在事务中重新读取，只修改路径
+    /// 路径不影响索引，索引不需要更新
+    pub fn finish(histories: &[RecordingHistory], save_path: &str) -> Result<()> {
+        let write_txn = db().begin_write()?;
+        {
+            let mut table = write_txn.open_table(HISTORIES)?;
+            for history in histories {
+                let key = (history.url.as_str(), history.start_time);
+                let current = table.get(key)?.map(|v| v.value().to_vec());
+                // 搬移期间被删除的不再写回
+                let Some(current) = current else {
+                    continue;
+                };
+                let mut current: RecordingHistory = serde_json::from_slice(&current)?;
+                current.path = history.path.clone();
+                if let (Some(trash), Some(moved)) = (&mut current.trash, &history.trash) {
+                    trash.path = moved.path.clone();
+                }
+                table.insert(key, &*serde_json::to_vec(&current)?)?;
+            }
+            let mut table = write_txn.open_table(TABLE)?;
+            let mut config: AppConfig = match table.get("config")? {
+                Some(config) => serde_json::from_slice(config.value())?,
+                None => AppConfig::default(),
+            };
+            config.save_path = save_path.into();
+            table.insert("config", &*serde_json::to_vec(&config)?)?;
+            table.remove(KEY)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+}
+
+pub mod maintenance {
+    use super::*;
+    use std::collections::HashSet;
+
+    // 设置后在下次启动时压缩数据库文件，运行中的数据库不能压缩
+    const COMPACT_KEY: &str = "compact_on_start";
+
+    // 数据库内部互相对不上的数据，检查时在读事务中收集，修复时在写事务中重新收集
+    struct Findings {
+        histories: Vec<RecordingHistory>,
+        // 没有对应录制任务的 recording 标记
+        recordings: Vec<(String, i64)>,
+        // 既没有计划也没有录制历史的直播间信息和主播
+        lives: Vec<String>,
+        // 录制历史已经不存在的记录
+        samples: Vec<(String, i64, i64)>,
+        broken_index_entries: u64,
+    }
+
+    /// 检查数据库，只读取，不阻塞其它写入；repair 为 true 时在一个写事务中修复发现的问题
+    /// is_running 判断一个 url 是否真的在录制，没有在录制的 recording 标记认为是遗留的
+    /// 文件不存在的录制历史只报告，修复时不删除，文件可能只是所在的磁盘没有挂载
+    pub fn check(is_running: impl Fn(&str) -> bool, repair: bool) -> Result<DbCheckReport> {
+        let findings = {
+            let read_txn = db().begin_read()?;
+            collect(
+                &read_txn.open_table(TABLE)?,
+                &read_txn.open_table(PLANS)?,
+                &read_txn.open_table(HISTORIES)?,
+                [&read_txn.open_table(LIVES)?, &read_txn.open_table(ANCHORS)?],
+                &read_txn.open_table(SAMPLES)?,
+                &read_txn.open_multimap_table(HISTORY_BY_START)?,
+                [
+                    &read_txn.open_multimap_table(HISTORY_BY_ANCHOR)?,
+                    &read_txn.open_multimap_table(HISTORY_BY_PLATFORM)?,
+                ],
+                &is_running,
+            )?
+        };
+        // 检查文件可能很慢，不在任何事务中进行
+        let missing_files = findings
+            .histories
+            .iter()
+            .filter(|h| {
+                h.status == crate::model::RecordStatus::NotRecording
+                    && !std::path::Path::new(h.file_path()).exists()
+            })
+            .cloned()
+            .collect();
+        if !repair {
+            return Ok(report(findings, missing_files, false));
+        }
+
+        let write_txn = db().begin_write()?;
+        // 检查之后数据库可能已经变化，按写事务中的数据修复
+        let findings = collect(
+            &write_txn.open_table(TABLE)?,
+            &write_txn.open_table(PLANS)?,
+            &write_txn.open_table(HISTORIES)?,
+            [
+                &write_txn.open_table(LIVES)?,
+                &write_txn.open_table(ANCHORS)?,
+            ],
+            &write_txn.open_table(SAMPLES)?,
+            &write_txn.open_multimap_table(HISTORY_BY_START)?,
+            [
+                &write_txn.open_multimap_table(HISTORY_BY_ANCHOR)?,
+                &write_txn.open_multimap_table(HISTORY_BY_PLATFORM)?,
+            ],
+            &is_running,
+        )?;
+        for (url, start_time) in &findings.recordings {
+            write_txn
+                .open_table(TABLE)?
+                .remove(format!("recording:{}", url).as_str())?;
+            let mut table = write_txn.open_table(HISTORIES)?;
+            let mut history: RecordingHistory = match table.get((url.as_str(), *start_time))? {
+                Some(history) => serde_json::from_slice(history.value())?,
+                // 录制历史已经不存在，只删除标记
+                None => continue,
+            };
+            history.end_time = Utc::now().timestamp_millis();
+            history.status = crate::model::RecordStatus::NotRecording;
+            table.insert((url.as_str(), *start_time), &*serde_json::to_vec(&history)?)?;
+        }
+        for url in &findings.lives {
+            write_txn.open_table(LIVES)?.remove(url.as_str())?;
+            write_txn.open_table(ANCHORS)?.remove(url.as_str())?;
+        }
+        {
+            let mut table = write_txn.open_table(SAMPLES)?;
+            for (url, start_time, time) in &findings.samples {
+                table.remove((url.as_str(), *start_time, *time))?;
+            }
+        }
+        // 索引直接按录制历史重建
+        write_txn.delete_multimap_table(HISTORY_BY_START)?;
+        write_txn.delete_multimap_table(HISTORY_BY_ANCHOR)?;
+        write_txn.delete_multimap_table(HISTORY_BY_PLATFORM)?;
+        write_txn.open_multimap_table(HISTORY_BY_START)?;
+        write_txn.open_multimap_table(HISTORY_BY_ANCHOR)?;
+        write_txn.open_multimap_table(HISTORY_BY_PLATFORM)?;
+        for history in &findings.histories {
+            history::index(&write_txn, history)?;
+        }
+        write_txn
+            .open_table(TABLE)?
+            .insert(COMPACT_KEY, b"".as_slice())?;
+        write_txn.commit()?;
+        let mut report = report(findings, missing_files, true);
+        report.compact_scheduled = true;
+        Ok(report)
+    }
+
+    fn report(
+        findings: Findings,
+        missing_files: Vec<RecordingHistory>,
+        repaired: bool,
+    ) -> DbCheckReport {
+        DbCheckReport {
+            orphaned_recordings: findings
+                .recordings
+                .into_iter()
+                .map(|(url, _)| url)
+                .collect(),
+            missing_files,
+            orphaned_lives: findings.lives,
+            orphaned_samples: findings.samples.len() as u64,
+            broken_index_entries: findings.broken_index_entries,
+            repaired,
+            compact_scheduled: false,
+        }
+    }
+
+    // 读事务和写事务打开的表类型不同，都按 ReadableTable 读取
+    #[allow(clippy::too_many_arguments)]
+    fn collect(
+        table: &impl ReadableTable<&'static str, &'static [u8]>,
+        plans: &impl ReadableTable<&'static str, &'static [u8]>,
+        histories: &impl ReadableTable<(&'static str, i64), &'static [u8]>,
+        lives: [&impl ReadableTable<&'static str, &'static [u8]>; 2],
+        samples: &impl ReadableTable<(&'static str, i64, i64), &'static [u8]>,
+        by_start: &impl ReadableMultimapTable<i64, &'static str>,
+        by_key: [&impl ReadableMultimapTable<&'static str, (i64, &'static str)>; 2],
+        is_running: &impl Fn(&str) -> bool,
+    ) -> Result<Findings> {
+        let mut all = vec![];
+        for kv in histories.iter()? {
+            let (_, history) = kv?;
+            all.push(serde_json::from_slice::<RecordingHistory>(history.value())?);
+        }
+        let histories = all;
+        let keys: HashSet<(String, i64)> = histories
+            .iter()
+            .map(|h| (h.url.clone(), h.start_time))
+            .collect();
+
+        let mut recordings = vec![];
+        for kv in table.range("recording:".."recordingz")? {
+            let (key, start_time) = kv?;
+            let url = key.value()["recording:".len()..].to_string();
+            if !is_running(&url) {
+                recordings.push((url, i64::from_be_bytes(start_time.value().try_into()?)));
+            }
+        }
+
+        let plans: HashSet<String> = plans
+            .iter()?
+            .map(|kv| Ok(kv?.0.value().to_string()))
+            .collect::<Result<_>>()?;
+        let history_urls: HashSet<&str> = histories.iter().map(|h| h.url.as_str()).collect();
+        let mut orphaned_lives: HashSet<String> = HashSet::new();
+        for table in lives {
+            for kv in table.iter()? {
+                let url = kv?.0.value().to_string();
+                if !plans.contains(&url) && !history_urls.contains(url.as_str()) {
+                    orphaned_lives.insert(url);
+                }
+            }
+        }
+        let mut lives: Vec<String> = orphaned_lives.into_iter().collect();
+        lives.sort();
+
+        let mut orphaned_samples = vec![];
+        for kv in samples.iter()? {
+            let (key, _) = kv?;
+            let (url, start_time, time) = key.value();
+            if !keys.contains(&(url.to_string(), start_time)) {
+                orphaned_samples.push((url.to_string(), start_time, time));
+            }
+        }
+
+        let broken_index_entries = broken_index_entries(by_start, by_key, &histories)?;
+        Ok(Findings {
+            histories,
+            recordings,
+            lives,
+            samples: orphaned_samples,
+            broken_index_entries,
+        })
+    }
+
+    // 和按录制历史计算出的索引对不上的条目数，包括多出来的和缺少的
+    fn broken_index_entries(
+        by_start_table: &impl ReadableMultimapTable<i64, &'static str>,
+        by_key_tables: [&impl ReadableMultimapTable<&'static str, (i64, &'static str)>; 2],
+        histories: &[RecordingHistory],
+    ) -> Result<u64> {
+        let mut by_start = HashSet::new();
+        let mut by_anchor = HashSet::new();
+        let mut by_platform = HashSet::new();
+        for history in histories {
+            let (anchor, platform) = history::index_keys(history);
+            by_start.insert((history.start_time, history.url.clone()));
+            by_anchor.insert((anchor, history.start_time, history.url.clone()));
+            by_platform.insert((platform, history.start_time, history.url.clone()));
+        }
+        let mut broken = 0;
+        for kv in by_start_table.iter()? {
+            let (start_time, urls) = kv?;
+            for url in urls {
+                let entry = (start_time.value(), url?.value().to_string());
+                if !by_start.remove(&entry) {
+                    broken += 1;
+                }
+            }
+        }
+        let [anchor_table, platform_table] = by_key_tables;
+        for (table, expected) in [
+            (anchor_table, &mut by_anchor),
+            (platform_table, &mut by_platform),
+        ] {
+            for kv in table.iter()? {
+                let (key, values) = kv?;
+                for value in values {
+                    let (start_time, url) = value?.value();
+                    let entry = (key.value().to_string(), start_time, url.to_string());
+                    if !expected.remove(&entry) {
+                        broken += 1;
+                    }
+                }
+            }
+        }
+        Ok(broken + (by_start.len() + by_anchor.len() + by_platform.len()) as u64)
+    }
+
+    /// 修复时设置了压缩标记的话，在数据库还没有被其它地方使用时压缩
+    pub(super) fn compact_if_requested(db: &mut Database) -> Result<()> {
+        let write_txn = db.begin_write()?;
+        let requested = write_txn.open_table(TABLE)?.remove(COMPACT_KEY)?.is_some();
+        write_txn.commit()?;
+        if requested {
+            println!("压缩数据库文件");
+            db.compact()?;
+        }
+        Ok(())
+    }
+}
+
+/// 数据库备份，在一个读事务中把所有表复制到新的数据库文件，复制时不影响写入
+pub mod backup {
+    use super::*;
+    use anyhow::{anyhow, Context};
+    use chrono::Local;
+    use redb::{Key, Value};
+    use std::path::{Path, PathBuf};
+
+    const PREFIX: &str = "liveship-";
+    // 等待恢复的备份，内容是暂存的备份文件的路径，下次启动时替换数据库文件
+    const PENDING_RESTORE: &str = "restore.pending";
+    // 登记恢复时把备份复制到这里，备份目录中的文件可能在启动前被轮换删除
+    const STAGED_RESTORE: &str = "restore.db";
+
+    /// 备份目录，没有配置时使用配置目录下的 backups
+    pub fn dir() -> Result<PathBuf> {
+        let config = config::get()?;
+        let dir = if config.backup_dir.is_empty() {
+            config_dir()?.join("backups")
+        } else {
+            PathBuf::from(&config.backup_dir)
+        };
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// 创建一个备份，然后按配置的个数删除旧的备份
+    pub fn create() -> Result<BackupInfo> {
+        let name = format!("{}{}.db", PREFIX, Local::now().format("%Y%m%d-%H%M%S"));
+        let path = dir()?.join(name);
+        // 先写到临时文件，写完再改名，避免留下不完整的备份
+        let tmp = path.with_extension("db.tmp");
+        if tmp.exists() {
+            std::fs::remove_file(&tmp)?;
+        }
+        if let Err(e) = snapshot(db(), &tmp) {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e);
+        }
+        std::fs::rename(&tmp, &path)?;
+        rotate(&dir()?, config::get()?.backup_count)?;
+        info(&path)
+    }
+
+    /// 所有备份，最新的在前
+    pub fn list() -> Result<Vec<BackupInfo>> {
+        list_in(&dir()?)
+    }
+
+    // 按个数删除旧的备份，至少保留最新的一个
+    fn rotate(dir: &Path, count: usize) -> Result<()> {
+        for old in list_in(dir)?.iter().skip(count.max(1)) {
+            std::fs::remove_file(&old.path)?;
+        }
+        Ok(())
+    }
+
+    fn list_in(dir: &Path) -> Result<Vec<BackupInfo>> {
+        let mut backups = vec![];
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if is_backup(&path) {
+                backups.push(info(&path)?);
+            }
+        }
+        // 文件名中的时间可以直接按字符串排序
+        backups.sort_by(|a, b| b.name.cmp(&a.name));
+        Ok(backups)
+    }
+
+    /// 检查备份后登记恢复，数据库正在使用，文件在下次启动时才替换
+    pub fn restore(name: &str) -> Result<()> {
+        let path = dir()?.join(name);
+        // 只接受备份目录中的文件名
+        if path.file_name().and_then(|n| n.to_str()) != Some(name)
+            || !is_backup(&path)
+            || !path.exists()
+        {
+            return Err(anyhow!("backup not found: {}", name));
+        }
+        validate(&path).with_context(|| format!("invalid backup {}", name))?;
+        stage(&path, &config_dir()?)
+    }
+
+    // 把备份复制到暂存文件，再写入恢复标记
+    fn stage(backup: &Path, config_dir: &Path) -> Result<()> {
+        let staged = config_dir.join(STAGED_RESTORE);
+        let tmp = staged.with_extension("db.tmp");
+        std::fs::copy(backup, &tmp).context("could not stage backup")?;
+        std::fs::rename(&tmp, &staged)?;
+        std::fs::write(
+            config_dir.join(PENDING_RESTORE),
+            staged.to_string_lossy().as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    /// 有登记的恢复时替换数据库文件，原来的文件保留为 liveship.db.before-restore.bak
+    pub(super) fn apply_pending_restore(path: &Path) -> Result<()> {
+        apply_restore(&config_dir()?, path)
+    }
+
+    fn apply_restore(config_dir: &Path, path: &Path) -> Result<()> {
+        let pending = config_dir.join(PENDING_RESTORE);
+        if !pending.exists() {
+            return Ok(());
+        }
+        let staged = PathBuf::from(std::fs::read_to_string(&pending)?);
+        // 暂存的文件不见了就放弃这次恢复，不能让每次启动都失败
+        if !staged.exists() {
+            eprintln!("要恢复的备份不存在，放弃恢复：{}", staged.to_string_lossy());
+            std::fs::remove_file(&pending)?;
+            return Ok(());
+        }
+        if path.exists() {
+            std::fs::copy(path, path.with_extension("db.before-restore.bak"))
+                .context("could not back up database before restore")?;
+        }
+        std::fs::copy(&staged, path).context("could not restore backup")?;
+        std::fs::remove_file(&pending)?;
+        std::fs::remove_file(&staged)?;
+        println!("已从备份恢复数据库");
+        Ok(())
+    }
+
+    fn is_backup(path: &Path) -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(PREFIX) && name.ends_with(".db"))
+    }
+
+    fn info(path: &Path) -> Result<BackupInfo> {
+        let metadata = std::fs::metadata(path)?;
+        let created_at = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_millis() as i64;
+        Ok(BackupInfo {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            path: path.to_string_lossy().to_string(),
+            size: metadata.len(),
+            created_at,
+        })
+    }
+
+    // 备份能打开，版本不比当前代码新，当前版本的计划和录制历史都能读取
+    // 旧版本的备份恢复后会在启动时迁移
+    fn validate(path: &Path) -> Result<()> {
+        let db = Database::open(path)?;
+        let version = migration::version(&db)?;
+        let latest = migration::latest();
+        if version > latest {
+            return Err(anyhow!(
+                "backup version {} is newer than supported version {}",
+                version,
+                latest
+            ));
+        }
+        if version == latest {
+            let read_txn = db.begin_read()?;
+            for kv in read_txn.open_table(PLANS)?.iter()? {
+                let (_, plan) = kv?;
+                serde_json::from_slice::<RecordingPlan>(plan.value())?;
+            }
+            for kv in read_txn.open_table(HISTORIES)?.iter()? {
+                let (_, history) = kv?;
+                serde_json::from_slice::<RecordingHistory>(history.value())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn snapshot(source: &Database, path: &Path) -> Result<()> {
+        let read_txn = source.begin_read()?;
+        let target = Database::create(path)?;
+        let write_txn = target.begin_write()?;
+        copy_table(&read_txn, &write_txn, TABLE)?;
+        copy_table(&read_txn, &write_txn, PLANS)?;
+        copy_table(&read_txn, &write_txn, HISTORIES)?;
+        copy_table(&read_txn, &write_txn, LIVES)?;
+        copy_table(&read_txn, &write_txn, ANCHORS)?;
+        copy_table(&read_txn, &write_txn, SAMPLES)?;
+        copy_multimap_table(&read_txn, &write_txn, HISTORY_BY_START)?;
+        copy_multimap_table(&read_txn, &write_txn, HISTORY_BY_ANCHOR)?;
+        copy_multimap_table(&read_txn, &write_txn, HISTORY_BY_PLATFORM)?;
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    fn copy_table<K: Key + 'static, V: Value + 'static>(
+        read_txn: &ReadTransaction,
+        write_txn: &WriteTransaction,
+        definition: TableDefinition<K, V>,
+    ) -> Result<()> {
+        let source = read_txn.open_table(definition)?;
+        let mut target = write_txn.open_table(definition)?;
+        for kv in source.iter()? {
+            let (key, value) = kv?;
+            target.insert(key.value(), value.value())?;
+        }
+        Ok(())
+    }
+
+    fn copy_multimap_table<K: Key + 'static, V: Key + 'static>(
+        read_txn: &ReadTransaction,
+        write_txn: &WriteTransaction,
+        definition: MultimapTableDefinition<K, V>,
+    ) -> Result<()> {
+        let source = read_txn.open_multimap_table(definition)?;
+        let mut target = write_txn.open_multimap_table(definition)?;
+        for kv in source.iter()? {
+            let (key, values) = kv?;
+            for value in values {
+                target.insert(key.value(), value?.value())?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        // 创建一个只有一个计划的数据库
+        fn create_db(path: &Path, url: &str) -> Database {
+            let db = Database::create(path).unwrap();
+            create_tables(&db).unwrap();
+            let write_txn = db.begin_write().unwrap();
+            {
+                let plan = RecordingPlan::new_with_url(url);
+                let mut table = write_txn.open_table(PLANS).unwrap();
+                table
+                    .insert(url, &*serde_json::to_vec(&plan).unwrap())
+                    .unwrap();
+            }
+            write_txn.commit().unwrap();
+            db
+        }
+
+        fn plan_urls(path: &Path) -> Vec<String> {
+            let db = Database::open(path).unwrap();
+            let read_txn = db.begin_read().unwrap();
+            let table = read_txn.open_table(PLANS).unwrap();
+            let urls = table.iter().unwrap();
+            urls.map(|kv| kv.unwrap().0.value().to_string()).collect()
+        }
+
+        #[test]
+        fn test_snapshot() {
+            let dir = crate::utils::test_dir("backup-snapshot");
+            let url = "https://live.douyin.com/1";
+            let db = create_db(&dir.join("liveship.db"), url);
+            let backup = dir.join("liveship-20240101-000000.db");
+            snapshot(&db, &backup).unwrap();
+            assert!(validate(&backup).is_ok());
+            assert_eq!(plan_urls(&backup), vec![url.to_string()]);
+            std::fs::remove_dir_all(&dir).ok();
+        }
+
+        #[test]
+        fn test_rotate() {
+            let dir = crate::utils::test_dir("backup-rotate");
+            for name in [
+                "liveship-20240101-000000.db",
+                "liveship-20240102-000000.db",
+                "liveship-20240103-000000.db",
+                "other.db",
+            ] {
+                std::fs::write(dir.join(name), b"").unwrap();
+            }
+            rotate(&dir, 2).unwrap();
+            let names: Vec<String> = list_in(&dir).unwrap().into_iter().map(|b| b.name).collect();
+            assert_eq!(
+                names,
+                vec!["liveship-20240103-000000.db", "liveship-20240102-000000.db"]
+            );
+            // 不是备份的文件不删除，个数为 0 时也保留最新的一个
+            assert!(dir.join("other.db").exists());
+            rotate(&dir, 0).unwrap();
+            assert_eq!(list_in(&dir).unwrap().len(), 1);
+            std::fs::remove_dir_all(&dir).ok();
+        }
+
+        #[test]
+        fn test_restore() {
+            let dir = crate::utils::test_dir("backup-restore");
+            let path = dir.join("liveship.db");
+            let backup = dir.join("liveship-20240101-000000.db");
+            drop(create_db(&path, "https://live.douyin.com/1"));
+            drop(create_db(&backup, "https://live.douyin.com/2"));
+
+            // 登记后备份被轮换删除，仍然从暂存的文件恢复
+            stage(&backup, &dir).unwrap();
+            std::fs::remove_file(&backup).unwrap();
+            apply_restore(&dir, &path).unwrap();
+            assert_eq!(plan_urls(&path), vec!["https://live.douyin.com/2"]);
+            assert_eq!(
+                plan_urls(&path.with_extension("db.before-restore.bak")),
+                vec!["https://live.douyin.com/1"]
+            );
+            assert!(!dir.join(PENDING_RESTORE).exists());
+            assert!(!dir.join(STAGED_RESTORE).exists());
+            // 没有登记的恢复时什么都不做
+            apply_restore(&dir, &path).unwrap();
+            assert_eq!(plan_urls(&path), vec!["https://live.douyin.com/2"]);
+            std::fs::remove_dir_all(&dir).ok();
+        }
+
+        #[test]
+        fn test_restore_missing_staged() {
+            let dir = crate::utils::test_dir("backup-restore-missing");
+            let path = dir.join("liveship.db");
+            let url = "https://live.douyin.com/1";
+            drop(create_db(&path, url));
+            std::fs::write(
+                dir.join(PENDING_RESTORE),
+                dir.join(STAGED_RESTORE).to_string_lossy().as_bytes(),
+            )
+            .unwrap();
+            // 暂存的文件不存在时放弃恢复，删除标记，数据库不变
+            apply_restore(&dir, &path).unwrap();
+            assert!(!dir.join(PENDING_RESTORE).exists());
+            assert_eq!(plan_urls(&path), vec![url.to_string()]);
+            std::fs::remove_dir_all(&dir).ok();
+        }
+    }
+}
+
+#[cfg(test)]
 mod tests {
+    // 不调用 init，它会打开配置目录中真正的数据库，并且和其它测试共用的 INSTANCE 冲突
     #[test]
     fn test_kv() {
-        super::init().unwrap();
+        let _guard = super::init_for_test();
+        super::config::get().unwrap();
     }
 }
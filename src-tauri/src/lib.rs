@@ -1,13 +1,21 @@
 use tauri::{AppHandle, Manager};
 
 pub mod backstage;
+mod condition;
 mod config;
+mod credential;
 mod ffmpeg;
 pub mod kv;
+mod library;
 mod manager;
 mod media;
 mod model;
+pub mod platform;
+mod quality;
 mod request;
+mod schedule;
+mod throttle;
+mod transfer;
 mod utils;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -35,9 +43,24 @@ pub fn run() {
             manager::record::start_record,
             manager::record::stop_record,
             manager::record::record_status,
+            manager::record::start_plan_record,
             manager::history::get_all_history,
+            manager::history::query_history,
+            manager::history::get_recording_stats,
+            manager::history::get_live_samples,
             manager::history::delete_history,
+            manager::history::delete_histories,
+            manager::history::restore_history,
+            manager::history::relocate_library,
+            manager::history::resume_relocation,
+            manager::history::get_relocation,
+            manager::history::rescan_library,
             manager::history::open_in_folder,
+            manager::database::db_check,
+            manager::database::db_repair,
+            manager::database::create_backup,
+            manager::database::get_backups,
+            manager::database::restore_backup,
             manager::query_history::add_query_history,
             manager::query_history::get_all_query_history,
             manager::query_history::delete_query_history,
@@ -46,9 +69,19 @@ pub fn run() {
             manager::plan::add_plan,
             manager::plan::add_plan_with_url,
             manager::plan::delete_plan,
+            manager::plan::update_plan,
+            manager::plan::update_group_plans,
+            manager::plan::delete_group_plans,
+            manager::plan::get_plan_groups,
+            manager::plan::set_plan_group,
+            manager::plan::delete_plan_group,
+            manager::plan::import_plans,
+            manager::plan::export_plans,
             manager::plan::update_plan_status,
             manager::plan::get_last_polling_time,
             manager::plan::get_plans_not_recording,
+            manager::plan::get_plans_to_check,
+            manager::plan::get_plan_schedules,
             manager::config::get_config,
             manager::config::set_config,
             manager::ffmpeg_api::check_ffmpeg_version,
@@ -61,6 +94,18 @@ pub fn run() {
             manager::request_api::request,
             manager::request_api::try_request_get_status,
             manager::request_api::request_post,
+            manager::platform_api::get_live_info,
+            manager::platform_api::request_for_platform,
+            manager::platform_api::get_cached_live_info,
+            manager::platform_api::get_custom_platforms,
+            manager::platform_api::add_custom_platform,
+            manager::platform_api::delete_custom_platform,
+            manager::platform_api::reload_custom_platforms,
+            manager::platform_api::check_external_resolver_version,
+            manager::credential_api::set_platform_cookie,
+            manager::credential_api::get_platform_credentials,
+            manager::credential_api::delete_platform_cookie,
+            manager::credential_api::validate_platform_cookie,
             manager::my_utils::get_youtube_info,
             manager::my_utils::get_system_proxy_config,
         ])
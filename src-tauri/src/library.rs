@@ -0,0 +1,983 @@
+use crate::{
+    backstage::MIN_SESSION_MILLIS_FOR_RESTART,
+    kv,
+    model::{
+        Anchor, HistoryPage, HistoryQuery, HistorySort, LiveInfo, PlatformKind, RecordStatus,
+        RecordingHistory, RecordingStats, RelocateProgress, Relocation, ScanReport, StatsBucket,
+        TrashInfo,
+    },
+    utils,
+};
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, Local, NaiveDateTime, TimeZone};
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// 每页默认的条数
+const DEFAULT_PAGE_SIZE: usize = 50;
+/// 统计中列出的最长录制条数
+const LONGEST_COUNT: usize = 10;
+
+/// 计算录制文件的尺寸，文件不存在时认为已经被删除，在回收站中的按回收站中的文件计算
+pub fn fill_file_info(history: &mut RecordingHistory) -> Result<()> {
+    if Path::new(history.file_path()).exists() {
+        history.file_size = std::fs::metadata(history.file_path())?.len();
+    } else {
+        history.deleted = true;
+    }
+    Ok(())
+}
+
+/// 按条件分页查询录制历史，只有返回的这一页会检查文件
+pub fn query(query: &HistoryQuery) -> Result<HistoryPage> {
+    let range = query.start_from.unwrap_or(i64::MIN)..query.start_to.unwrap_or(i64::MAX);
+    // 先用索引缩小范围，其它条件在内存中过滤
+    let mut histories = match (&query.platform_kind, &query.anchor_name) {
+        (Some(platform_kind), _) => kv::history::get_by_platform(platform_kind)?,
+        (None, Some(anchor_name)) => kv::history::get_by_anchor(anchor_name)?,
+        (None, None) => kv::history::get_by_time(range.clone())?,
+    };
+    let title = query.title.as_ref().map(|t| t.to_lowercase());
+    histories.retain(|h| {
+        range.contains(&h.start_time)
+            && h.trash.is_some() == query.trashed
+            && query.status.as_ref().is_none_or(|s| h.status == *s)
+            && query
+                .anchor_name
+                .as_ref()
+                .is_none_or(|name| h.live_info.as_ref().is_some_and(|l| l.anchor_name == *name))
+            && title.as_ref().is_none_or(|title| {
+                h.live_info
+                    .as_ref()
+                    .is_some_and(|l| l.title.to_lowercase().contains(title))
+            })
+    });
+    // 按尺寸过滤时只能检查所有候选的文件
+    if let Some(min_size) = query.min_size {
+        for history in &mut histories {
+            fill_file_info(history)?;
+        }
+        histories.retain(|h| h.file_size >= min_size);
+    }
+    histories.sort_by_cached_key(|h| sort_key(h, &query.sort));
+    let total = histories.len();
+
+    let start = match &query.cursor {
+        Some(cursor) => {
+            let cursor = parse_cursor(cursor)?;
+            histories.partition_point(|h| sort_key(h, &query.sort) <= cursor)
+        }
+        None => 0,
+    };
+    let limit = if query.limit == 0 {
+        DEFAULT_PAGE_SIZE
+    } else {
+        query.limit
+    };
+    let mut items: Vec<RecordingHistory> = histories.into_iter().skip(start).take(limit).collect();
+    if query.min_size.is_none() {
+        for history in &mut items {
+            fill_file_info(history)?;
+        }
+    }
+    let next_cursor = match items.last() {
+        Some(last) if start + items.len() < total => {
+            Some(format_cursor(&sort_key(last, &query.sort)))
+        }
+        _ => None,
+    };
+    Ok(HistoryPage {
+        items,
+        total,
+        next_cursor,
+    })
+}
+
+/// 删除一条录制历史，delete_file 为 true 时把文件移到回收站，已经在回收站中的彻底删除
+/// 只删除记录时回收站中的文件也一起删除，避免留下没有记录的文件
+pub fn delete(url: &str, start_time: i64, delete_file: bool) -> Result<()> {
+    let history = kv::history::get(url, start_time)?;
+    match (&history.trash, delete_file) {
+        (None, true) if Path::new(&history.path).exists() => return trash(&history),
+        (Some(trash), _) => remove_file_if_exists(&trash.path)?,
+        _ => {}
+    }
+    kv::history::delete(url, start_time)
+}
+
+/// 把回收站中的文件移回原来的位置
+pub fn restore(url: &str, start_time: i64) -> Result<()> {
+    let history = kv::history::get(url, start_time)?;
+    let Some(trash) = &history.trash else {
+        return Err(anyhow!("recording is not in trash"));
+    };
+    if Path::new(&history.path).exists() {
+        return Err(anyhow!("file already exists: {}", history.path));
+    }
+    utils::move_file(Path::new(&trash.path), Path::new(&history.path))?;
+    kv::history::update(url, start_time, |h| h.trash = None)?;
+    Ok(())
+}
+
+/// 彻底删除在回收站中超过 retention_days 天的录制，返回删除的条数
+/// 某一条删除失败时记录下来继续删除其它的，下次清理时再重试
+pub fn purge_trash(retention_days: u64) -> Result<usize> {
+    let deadline = chrono::Utc::now().timestamp_millis() - retention_days as i64 * 86_400_000;
+    let mut purged = 0;
+    for history in kv::history::get_all()? {
+        let Some(trash) = &history.trash else {
+            continue;
+        };
+        if trash.trashed_at >= deadline {
+            continue;
+        }
+        let result = remove_file_if_exists(&trash.path)
+            .and_then(|_| kv::history::delete(&history.url, history.start_time));
+        match result {
+            Ok(_) => purged += 1,
+            Err(e) => eprintln!("Could not purge {}: {}", trash.path, e),
+        }
+    }
+    Ok(purged)
+}
+
+// 回收站在保存路径下，和录制文件在同一个设备上，移动时不需要复制
+fn trash_dir() -> Result<PathBuf> {
+    Ok(Path::new(&kv::config::get()?.save_path).join(".trash"))
+}
+
+fn trash(history: &RecordingHistory) -> Result<()> {
+    let from = Path::new(&history.path);
+    let name = from
+        .file_name()
+        .ok_or_else(|| anyhow!("invalid path: {}", history.path))?
+        .to_string_lossy();
+    // 文件名加上开始时间，避免和回收站中同名的文件冲突
+    let to = trash_dir()?.join(format!("{}_{}", history.start_time, name));
+    utils::move_file(from, &to)?;
+    let trash = TrashInfo {
+        path: to.to_string_lossy().to_string(),
+        trashed_at: chrono::Utc::now().timestamp_millis(),
+    };
+    if let Err(e) = kv::history::update(&history.url, history.start_time, |h| h.trash = Some(trash))
+    {
+        // 记录没有更新时把文件移回去，不留下找不到的文件
+        utils::move_file(&to, from)?;
+        return Err(e);
+    }
+    Ok(())
+}
+
+fn remove_file_if_exists(path: &str) -> Result<()> {
+    if Path::new(path).exists() {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// 把录制文件从当前的保存路径搬到 to，copy 为 true 时保留原来的文件
+/// 任务先保存到数据库，中断后用 resume_relocation 继续
+pub fn relocate(to: &str, copy: bool, progress: impl Fn(RelocateProgress)) -> Result<()> {
+    if let Some(relocation) = kv::relocation::get()? {
+        return Err(anyhow!("relocation to {} is not finished", relocation.to));
+    }
+    let from = kv::config::get()?.save_path;
+    if Path::new(to) == Path::new(&from) {
+        return Err(anyhow!("{} is already the save path", to));
+    }
+    let relocation = Relocation {
+        from,
+        to: to.into(),
+        copy,
+    };
+    kv::relocation::set(&relocation)?;
+    run_relocation(&relocation, progress)
+}
+
+/// 继续上次中断的搬移任务
+pub fn resume_relocation(progress: impl Fn(RelocateProgress)) -> Result<()> {
+    let relocation =
+        kv::relocation::get()?.ok_or_else(|| anyhow!("there is no relocation to resume"))?;
+    run_relocation(&relocation, progress)
+}
+
+// 逐个搬移原保存路径下的文件，保持相对路径，回收站中的文件一起搬移
+// 全部搬完后在一个事务中更新录制历史的路径和配置中的保存路径
+fn run_relocation(relocation: &Relocation, progress: impl Fn(RelocateProgress)) -> Result<()> {
+    let from = Path::new(&relocation.from);
+    let to = Path::new(&relocation.to);
+    let mut histories: Vec<RecordingHistory> = kv::history::get_all()?
+        .into_iter()
+        .filter(|h| {
+            Path::new(&h.path).starts_with(from) || Path::new(h.file_path()).starts_with(from)
+        })
+        .collect();
+    let total = histories.len();
+    for (done, history) in histories.iter_mut().enumerate() {
+        progress(RelocateProgress {
+            total,
+            done,
+            path: history.file_path().to_string(),
+        });
+        let source = PathBuf::from(history.file_path());
+        if let Some(target) = rebase(&source, from, to) {
+            relocate_file(&source, &target, relocation.copy)?;
+        }
+        if let Some(path) = rebase(Path::new(&history.path), from, to) {
+            history.path = path.to_string_lossy().to_string();
+        }
+        if let Some(trash) = &mut history.trash {
+            if let Some(path) = rebase(Path::new(&trash.path), from, to) {
+                trash.path = path.to_string_lossy().to_string();
+            }
+        }
+    }
+    kv::relocation::finish(&histories, &relocation.to)?;
+    progress(RelocateProgress {
+        total,
+        done: total,
+        path: "".into(),
+    });
+    Ok(())
+}
+
+// from 下的路径换到 to 下，不在 from 下时返回 None
+fn rebase(path: &Path, from: &Path, to: &Path) -> Option<PathBuf> {
+    path.strip_prefix(from)
+        .ok()
+        .map(|relative| to.join(relative))
+}
+
+// 搬移一个文件，可以重复执行：源文件不在时认为已经搬过了，目标文件尺寸相同认为已经复制过了
+// 尺寸不同的目标文件是上次中断留下的，直接替换
+fn relocate_file(source: &Path, target: &Path, copy: bool) -> Result<()> {
+    if !source.exists() {
+        return Ok(());
+    }
+    let copied =
+        target.exists() && std::fs::metadata(target)?.len() == std::fs::metadata(source)?.len();
+    match (copy, copied) {
+        (true, true) => Ok(()),
+        (true, false) => utils::copy_file(source, target),
+        // 跨设备移动时复制完成但还没有删除源文件就中断了
+        (false, true) => Ok(std::fs::remove_file(source)?),
+        (false, false) => utils::move_file(source, target),
+    }
+}
+
+/// 录制文件的扩展名，扫描时只处理这些文件
+const MEDIA_EXTENSIONS: &[&str] = &["ts", "flv", "mp4", "mkv", "mov", "webm"];
+/// 计算指纹时读取文件开头和结尾的字节数
+const FINGERPRINT_BYTES: u64 = 64 * 1024;
+
+/// 结束录制，同时计算录制文件的指纹，文件以后被移动了扫描时也能找到
+/// 指纹算不出来不影响结束录制，扫描时会再补上
+pub fn end_recording(url: &str) -> Result<()> {
+    let history = kv::history::end(url)?;
+    let path = Path::new(history.file_path());
+    if !path.exists() {
+        return Ok(());
+    }
+    match fingerprint(path) {
+        Ok(fingerprint) => {
+            kv::history::update(url, history.start_time, |h| h.fingerprint = fingerprint)?;
+        }
+        Err(e) => eprintln!("Could not fingerprint {}: {}", history.file_path(), e),
+    }
+    Ok(())
+}
+
+/// 扫描保存路径：找回被移动过的录制文件，把不认识的录制文件导入为录制历史
+/// 读取不了的文件放到 skipped 中，不中断扫描
+/// dry_run 为 true 时只返回结果，不修改数据库
+pub fn rescan(dry_run: bool) -> Result<ScanReport> {
+    let root = PathBuf::from(kv::config::get()?.save_path);
+    let mut files = vec![];
+    walk(&root, &mut files)?;
+    let mut histories = kv::history::get_all()?;
+    let known: HashSet<PathBuf> = histories
+        .iter()
+        .map(|h| PathBuf::from(h.file_path()))
+        .collect();
+    let mut unknown: Vec<PathBuf> = files
+        .iter()
+        .filter(|f| !known.contains(*f))
+        .cloned()
+        .collect();
+    let mut report = ScanReport {
+        dry_run,
+        scanned: files.len(),
+        ..Default::default()
+    };
+
+    // 文件还在的录制历史补上指纹，以后文件被移动了也能找到
+    let mut updated = vec![];
+    for history in &mut histories {
+        let path = Path::new(history.file_path());
+        if history.fingerprint.is_empty()
+            && history.status == RecordStatus::NotRecording
+            && path.exists()
+        {
+            match fingerprint(path) {
+                Ok(fingerprint) => {
+                    history.fingerprint = fingerprint;
+                    updated.push(history.clone());
+                }
+                Err(_) => report.skipped.push(history.file_path().to_string()),
+            }
+        }
+    }
+
+    // 文件不在了的录制历史，先按文件名找，再按同名的其它格式找，例如转换后的 mp4，最后按指纹找
+    // 读取不了的文件指纹是 None
+    let mut fingerprints: HashMap<PathBuf, Option<String>> = HashMap::new();
+    for history in histories.iter_mut().filter(|h| {
+        h.trash.is_none() && h.status == RecordStatus::NotRecording && !Path::new(&h.path).exists()
+    }) {
+        let path = Path::new(&history.path);
+        let mut found = unknown
+            .iter()
+            .position(|f| f.file_name() == path.file_name())
+            .or_else(|| {
+                unknown
+                    .iter()
+                    .position(|f| f.parent() == path.parent() && f.file_stem() == path.file_stem())
+            });
+        if found.is_none() && !history.fingerprint.is_empty() {
+            for (i, file) in unknown.iter().enumerate() {
+                let file_fingerprint = match fingerprints.entry(file.clone()) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(fingerprint(file).ok()),
+                };
+                if file_fingerprint.as_ref() == Some(&history.fingerprint) {
+                    found = Some(i);
+                    break;
+                }
+            }
+        }
+        match found {
+            Some(i) => {
+                history.path = unknown.remove(i).to_string_lossy().to_string();
+                updated.push(history.clone());
+                report.relinked.push(history.clone());
+            }
+            None => report.missing.push(history.clone()),
+        }
+    }
+
+    // 剩下的文件导入为新的录制历史，和已知录制同目录同名的文件是转换出来的，不导入
+    let anchors = kv::anchor::get_all()?;
+    let mut keys: HashSet<(String, i64)> = histories
+        .iter()
+        .map(|h| (h.url.clone(), h.start_time))
+        .collect();
+    for file in unknown {
+        let derived = histories.iter().any(|h| {
+            let path = Path::new(h.file_path());
+            path.parent() == file.parent() && path.file_stem() == file.file_stem()
+        });
+        let unreadable = fingerprints.get(&file).is_some_and(|f| f.is_none());
+        if derived || unreadable {
+            report.skipped.push(file.to_string_lossy().to_string());
+            continue;
+        }
+        let Ok(history) = parse_file(&root, &file, &anchors) else {
+            report.skipped.push(file.to_string_lossy().to_string());
+            continue;
+        };
+        if !keys.insert((history.url.clone(), history.start_time)) {
+            report.skipped.push(file.to_string_lossy().to_string());
+            continue;
+        }
+        report.imported.push(history);
+    }
+
+    if !dry_run {
+        kv::history::relink(&updated)?;
+        kv::history::import(&report.imported)?;
+    }
+    Ok(report)
+}
+
+// 递归列出目录下的录制文件，跳过 . 开头的目录，例如回收站
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            let hidden = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if !hidden {
+                walk(&path, files)?;
+            }
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| MEDIA_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+// 文件尺寸加上开头和结尾各 64KB 的 FNV-1a 哈希，不需要读完整个文件
+fn fingerprint(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for offset in [0, size.saturating_sub(FINGERPRINT_BYTES)] {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut chunk = vec![];
+        (&mut file)
+            .take(FINGERPRINT_BYTES)
+            .read_to_end(&mut chunk)?;
+        for byte in chunk {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    Ok(format!("{}-{:016x}", size, hash))
+}
+
+// 按 generate_path 的目录结构解析录制文件：保存路径/平台/主播/主播_时间.ts
+// 能对应到已知主播时使用主播的直播间网址，否则用文件路径代替
+fn parse_file(root: &Path, file: &Path, anchors: &[Anchor]) -> Result<RecordingHistory> {
+    let relative: Vec<String> = file
+        .strip_prefix(root)?
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    let (platform_kind, folder) = match relative.as_slice() {
+        [platform, anchor, _] => (parse_platform(platform), anchor.clone()),
+        _ => (PlatformKind::Unknown, "".to_string()),
+    };
+    let metadata = std::fs::metadata(file)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_millis() as i64;
+    let start_time = file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(parse_filename_time)
+        .unwrap_or(modified);
+    let anchor = anchors.iter().find(|anchor| {
+        !folder.is_empty()
+            && anchor.platform_kind == platform_kind
+            && utils::anchor_folder_name(&anchor.anchor_name) == folder
+    });
+    let path = file.to_string_lossy().to_string();
+    let live_info = match anchor {
+        Some(anchor) => {
+            let mut live_info = LiveInfo::new(&anchor.url, anchor.platform_kind.clone());
+            live_info.anchor_name = anchor.anchor_name.clone();
+            live_info.anchor_avatar = anchor.anchor_avatar.clone();
+            live_info
+        }
+        None => {
+            let mut live_info = LiveInfo::new(&format!("file://{}", path), platform_kind);
+            live_info.anchor_name = folder;
+            live_info
+        }
+    };
+    let mut history = RecordingHistory::new(&live_info.url, &path, live_info.platform_kind.clone());
+    history.status = RecordStatus::NotRecording;
+    history.start_time = start_time;
+    history.end_time = modified.max(start_time);
+    history.file_size = metadata.len();
+    history.live_info = Some(live_info);
+    Ok(history)
+}
+
+// 平台文件夹的名字是 PlatformKind 的名字，自定义平台是定义里的平台名
+fn parse_platform(name: &str) -> PlatformKind {
+    PlatformKind::builtin()
+        .find(|kind| kind.to_string() == name)
+        .unwrap_or_else(|| PlatformKind::Custom(name.into()))
+}
+
+// generate_filename 生成的文件名是 主播_20240601_200000，时间是本地时间
+fn parse_filename_time(stem: &str) -> Option<i64> {
+    let mut parts = stem.rsplitn(3, '_');
+    let time = parts.next()?;
+    let date = parts.next()?;
+    let time =
+        NaiveDateTime::parse_from_str(&format!("{}_{}", date, time), "%Y%m%d_%H%M%S").ok()?;
+    Local
+        .from_local_datetime(&time)
+        .single()
+        .map(|time| time.timestamp_millis())
+}
+
+/// 统计开始时间在范围内的录制，已删除的文件按 0 字节计算
+pub fn stats(start_from: Option<i64>, start_to: Option<i64>) -> Result<RecordingStats> {
+    let range = start_from.unwrap_or(i64::MIN)..start_to.unwrap_or(i64::MAX);
+    let mut histories = kv::history::get_by_time(range)?;
+    for history in &mut histories {
+        fill_file_info(history)?;
+    }
+    Ok(aggregate(histories, chrono::Utc::now().timestamp_millis()))
+}
+
+// 录制时长，还在录制的算到 now 为止
+fn duration(history: &RecordingHistory, now: i64) -> i64 {
+    let end_time = if history.end_time == 0 {
+        now
+    } else {
+        history.end_time
+    };
+    (end_time - history.start_time).max(0)
+}
+
+fn aggregate(histories: Vec<RecordingHistory>, now: i64) -> RecordingStats {
+    let mut total = StatsBucket::default();
+    let mut by_anchor = BTreeMap::new();
+    let mut by_platform = BTreeMap::new();
+    let mut by_day = BTreeMap::new();
+    let mut by_week = BTreeMap::new();
+    for history in &histories {
+        // 和索引一样，优先使用录制历史中保存的平台
+        let (anchor, platform) = kv::history::index_keys(history);
+        let (day, week) = match Local.timestamp_millis_opt(history.start_time).single() {
+            Some(time) => {
+                let week = time.iso_week();
+                (
+                    time.format("%Y-%m-%d").to_string(),
+                    format!("{}-W{:02}", week.year(), week.week()),
+                )
+            }
+            None => ("".into(), "".into()),
+        };
+        let duration = duration(history, now);
+        for (buckets, key) in [
+            (&mut by_anchor, anchor),
+            (&mut by_platform, platform),
+            (&mut by_day, day),
+            (&mut by_week, week),
+        ] {
+            buckets
+                .entry(key.clone())
+                .or_insert_with(|| StatsBucket {
+                    key,
+                    ..Default::default()
+                })
+                .add(history, duration);
+        }
+        total.add(history, duration);
+    }
+    let mut longest = histories;
+    longest.sort_by_key(|h| -duration(h, now));
+    longest.truncate(LONGEST_COUNT);
+    RecordingStats {
+        total,
+        by_anchor: by_anchor.into_values().collect(),
+        by_platform: by_platform.into_values().collect(),
+        by_day: by_day.into_values().collect(),
+        by_week: by_week.into_values().collect(),
+        longest,
+    }
+}
+
+impl StatsBucket {
+    fn add(&mut self, history: &RecordingHistory, duration: i64) {
+        self.sessions += 1;
+        self.duration += duration;
+        self.bytes += history.file_size;
+        // 很快就退出的录制认为是失败的
+        if history.end_time != 0 && duration < MIN_SESSION_MILLIS_FOR_RESTART {
+            self.failures += 1;
+        }
+        self.average_duration = self.duration / self.sessions as i64;
+    }
+}
+
+// 排序用的键，第一项按排序方式计算，后两项保证唯一
+fn sort_key(history: &RecordingHistory, sort: &HistorySort) -> (i64, i64, String) {
+    let primary = match sort {
+        HistorySort::StartTimeDesc => -history.start_time,
+        HistorySort::StartTimeAsc => history.start_time,
+        // 还在录制的按到现在为止的时长
+        HistorySort::DurationDesc => {
+            let end_time = if history.end_time == 0 {
+                chrono::Utc::now().timestamp_millis()
+            } else {
+                history.end_time
+            };
+            history.start_time - end_time
+        }
+    };
+    (primary, history.start_time, history.url.clone())
+}
+
+// 游标是上一页最后一条的排序键
+fn format_cursor(key: &(i64, i64, String)) -> String {
+    format!("{}:{}:{}", key.0, key.1, key.2)
+}
+
+fn parse_cursor(cursor: &str) -> Result<(i64, i64, String)> {
+    let mut parts = cursor.splitn(3, ':');
+    let mut next = || {
+        parts
+            .next()
+            .ok_or_else(|| anyhow!("invalid cursor: {}", cursor))
+    };
+    let primary = next()?.parse()?;
+    let start_time = next()?.parse()?;
+    let url = next()?.to_string();
+    Ok((primary, start_time, url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 使用测试数据库，保存路径设置为新的临时目录，在里面放一个录制文件并导入它的录制历史
+    fn setup(
+        name: &str,
+    ) -> (
+        std::sync::MutexGuard<'static, ()>,
+        PathBuf,
+        RecordingHistory,
+    ) {
+        let guard = kv::init_for_test();
+        let dir = crate::utils::test_dir(name);
+        let mut config = kv::config::get().unwrap();
+        config.save_path = dir.to_string_lossy().to_string();
+        kv::config::set(&config).unwrap();
+        let path = dir.join("Douyin").join("张三").join("1.ts");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, b"video").unwrap();
+        let url = format!("https://live.douyin.com/{}", name);
+        let mut history =
+            RecordingHistory::new(&url, &path.to_string_lossy(), PlatformKind::Douyin);
+        history.status = RecordStatus::NotRecording;
+        history.end_time = history.start_time + 1000;
+        kv::history::import(&[history.clone()]).unwrap();
+        (guard, dir, history)
+    }
+
+    #[test]
+    fn test_relocate_and_resume() {
+        let _guard = kv::init_for_test();
+        let dir = crate::utils::test_dir("relocate");
+        let (from, to) = (dir.join("from"), dir.join("to"));
+        let mut config = kv::config::get().unwrap();
+        config.save_path = from.to_string_lossy().to_string();
+        kv::config::set(&config).unwrap();
+        let mut histories = vec![];
+        for (start_time, name) in [(1000, "1.ts"), (2000, "2.ts")] {
+            let path = from.join("Douyin").join("张三").join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, name).unwrap();
+            let mut history = RecordingHistory::new(
+                "https://live.douyin.com/relocate",
+                &path.to_string_lossy(),
+                PlatformKind::Douyin,
+            );
+            history.start_time = start_time;
+            history.end_time = start_time + 1000;
+            history.status = RecordStatus::NotRecording;
+            histories.push(history);
+        }
+        kv::history::import(&histories).unwrap();
+        let target = |name: &str| to.join("Douyin").join("张三").join(name);
+
+        // 后开始的先搬，先开始的目标位置是一个目录，不能替换，搬移中断
+        std::fs::create_dir_all(target("1.ts")).unwrap();
+        assert!(relocate(&to.to_string_lossy(), false, |_| {}).is_err());
+        assert!(target("2.ts").exists());
+        assert!(from.join("Douyin").join("张三").join("1.ts").exists());
+        assert!(target("1.ts").is_dir());
+        assert!(kv::relocation::get().unwrap().is_some());
+        // 任务没有完成，录制历史和保存路径都不变
+        assert_eq!(
+            kv::history::get("https://live.douyin.com/relocate", 2000)
+                .unwrap()
+                .path,
+            histories[1].path
+        );
+        assert_eq!(kv::config::get().unwrap().save_path, config.save_path);
+        assert!(relocate(&to.to_string_lossy(), false, |_| {}).is_err());
+
+        // 移走冲突的目录后继续，已经搬过的跳过，上次中断留下的不完整文件被替换
+        std::fs::remove_dir(target("1.ts")).unwrap();
+        std::fs::write(target("1.ts"), "1").unwrap();
+        resume_relocation(|_| {}).unwrap();
+        assert!(kv::relocation::get().unwrap().is_none());
+        assert_eq!(kv::config::get().unwrap().save_path, to.to_string_lossy());
+        for (start_time, name) in [(1000, "1.ts"), (2000, "2.ts")] {
+            let history = kv::history::get("https://live.douyin.com/relocate", start_time).unwrap();
+            assert_eq!(history.path, target(name).to_string_lossy());
+            assert_eq!(std::fs::read_to_string(target(name)).unwrap(), name);
+            kv::history::delete(&history.url, start_time).unwrap();
+        }
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_relocate_file_interrupted() {
+        let dir = crate::utils::test_dir("relocate-file");
+        let (source, target) = (dir.join("from.ts"), dir.join("to").join("to.ts"));
+        std::fs::write(&source, "video").unwrap();
+        std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+        // 复制完成但源文件还没有删除，继续时只删除源文件
+        std::fs::write(&target, "video").unwrap();
+        relocate_file(&source, &target, false).unwrap();
+        assert!(!source.exists());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "video");
+        // 不完整的目标文件被替换
+        std::fs::write(&source, "video").unwrap();
+        std::fs::write(&target, "vi").unwrap();
+        relocate_file(&source, &target, true).unwrap();
+        assert!(source.exists());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "video");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_rescan() {
+        let _guard = kv::init_for_test();
+        let root = crate::utils::test_dir("rescan");
+        let mut config = kv::config::get().unwrap();
+        config.save_path = root.to_string_lossy().to_string();
+        kv::config::set(&config).unwrap();
+        let file = |folder: &str, name: &str, content: &str| {
+            let path = root.join("Douyin").join(folder).join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, content).unwrap();
+            path
+        };
+        let history = |url: &str, start_time: i64, path: &Path| {
+            let mut history =
+                RecordingHistory::new(url, &path.to_string_lossy(), PlatformKind::Douyin);
+            history.start_time = start_time;
+            history.end_time = start_time + 1000;
+            history.status = RecordStatus::NotRecording;
+            history
+        };
+        // 文件被移到了别的主播文件夹，按文件名找回
+        let moved = file("李四", "张三_20240601_200000.ts", "name");
+        let by_name = history(
+            "https://live.douyin.com/rescan-name",
+            1000,
+            &root
+                .join("Douyin")
+                .join("张三")
+                .join("张三_20240601_200000.ts"),
+        );
+        // 文件被改了名，按指纹找回
+        let renamed = file("张三", "renamed.ts", "hash");
+        let mut by_hash = history(
+            "https://live.douyin.com/rescan-hash",
+            2000,
+            &root.join("Douyin").join("张三").join("old.ts"),
+        );
+        by_hash.fingerprint = fingerprint(&renamed).unwrap();
+        // 已知录制转换出来的同名文件不导入
+        let known_path = file("王五", "王五_20240601_210000.ts", "known");
+        let known = history("https://live.douyin.com/rescan-known", 3000, &known_path);
+        let derived = file("王五", "王五_20240601_210000.mp4", "derived");
+        // 不认识的文件导入
+        let new = file("赵六", "赵六_20240602_200000.flv", "new");
+        kv::history::import(&[by_name.clone(), by_hash.clone(), known.clone()]).unwrap();
+        // 读取时会用直播间信息补上录制历史中没有的主播，写回时不能带上，否则索引对不上
+        let mut live = LiveInfo::new(&by_name.url, PlatformKind::Douyin);
+        live.anchor_name = "张三".into();
+        kv::live::add(&live).unwrap();
+
+        let report = rescan(false).unwrap();
+        let relinked = |url: &str| report.relinked.iter().find(|h| h.url == url).unwrap();
+        assert_eq!(relinked(&by_name.url).path, moved.to_string_lossy());
+        assert_eq!(relinked(&by_hash.url).path, renamed.to_string_lossy());
+        assert!(report
+            .skipped
+            .contains(&derived.to_string_lossy().to_string()));
+        assert_eq!(report.imported.len(), 1);
+        let imported = &report.imported[0];
+        assert_eq!(imported.path, new.to_string_lossy());
+        assert_eq!(imported.platform_kind, Some(PlatformKind::Douyin));
+        assert_eq!(
+            imported.start_time,
+            parse_filename_time("赵六_20240602_200000").unwrap()
+        );
+        // 结果写回了数据库，已知的录制补上了指纹
+        let path = |url: &str, start_time: i64| kv::history::get(url, start_time).unwrap().path;
+        assert_eq!(path(&by_name.url, 1000), moved.to_string_lossy());
+        assert_eq!(path(&by_hash.url, 2000), renamed.to_string_lossy());
+        assert!(!kv::history::get(&known.url, 3000)
+            .unwrap()
+            .fingerprint
+            .is_empty());
+        assert_eq!(path(&imported.url, imported.start_time), imported.path);
+        let check = kv::maintenance::check(|_| false, false).unwrap();
+        assert_eq!(check.broken_index_entries, 0);
+
+        for (url, start_time) in [
+            (by_name.url.as_str(), 1000),
+            (by_hash.url.as_str(), 2000),
+            (known.url.as_str(), 3000),
+            (imported.url.as_str(), imported.start_time),
+        ] {
+            kv::history::delete(url, start_time).unwrap();
+        }
+        kv::live::delete(&by_name.url).unwrap();
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_trash_and_restore() {
+        let (_guard, dir, history) = setup("trash-restore");
+        let (url, start_time) = (history.url.as_str(), history.start_time);
+        delete(url, start_time, true).unwrap();
+        let trashed = kv::history::get(url, start_time).unwrap();
+        let trash_path = trashed.trash.as_ref().unwrap().path.clone();
+        assert!(trash_path.starts_with(&*dir.join(".trash").to_string_lossy()));
+        assert!(Path::new(&trash_path).exists());
+        assert!(!Path::new(&history.path).exists());
+
+        restore(url, start_time).unwrap();
+        assert!(kv::history::get(url, start_time).unwrap().trash.is_none());
+        assert!(Path::new(&history.path).exists());
+        assert!(!Path::new(&trash_path).exists());
+        // 不在回收站中的不能恢复
+        assert!(restore(url, start_time).is_err());
+        kv::history::delete(url, start_time).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_purge_trash() {
+        let (_guard, dir, history) = setup("trash-purge");
+        let (url, start_time) = (history.url.as_str(), history.start_time);
+        delete(url, start_time, true).unwrap();
+        let trash_path = kv::history::get(url, start_time)
+            .unwrap()
+            .trash
+            .unwrap()
+            .path;
+        // 还没有超过保留天数
+        purge_trash(30).unwrap();
+        assert!(kv::history::get(url, start_time).is_ok());
+        assert!(Path::new(&trash_path).exists());
+
+        kv::history::update(url, start_time, |h| {
+            h.trash.as_mut().unwrap().trashed_at -= 31 * 86_400_000
+        })
+        .unwrap();
+        // 回收站中的路径是目录，删除失败，不影响删除其它的
+        let broken_dir = dir.join("broken");
+        std::fs::create_dir_all(&broken_dir).unwrap();
+        let mut broken = history.clone();
+        broken.url = format!("{}-broken", url);
+        broken.trash = Some(TrashInfo {
+            path: broken_dir.to_string_lossy().to_string(),
+            trashed_at: 0,
+        });
+        kv::history::import(&[broken.clone()]).unwrap();
+        assert!(purge_trash(30).unwrap() >= 1);
+        assert!(kv::history::get(url, start_time).is_err());
+        assert!(!Path::new(&trash_path).exists());
+        assert!(kv::history::get(&broken.url, broken.start_time).is_ok());
+        kv::history::delete(&broken.url, broken.start_time).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_delete_trashed() {
+        let (_guard, dir, history) = setup("trash-delete");
+        let (url, start_time) = (history.url.as_str(), history.start_time);
+        delete(url, start_time, true).unwrap();
+        let trash_path = kv::history::get(url, start_time)
+            .unwrap()
+            .trash
+            .unwrap()
+            .path;
+        // 已经在回收站中的彻底删除，文件和记录都不留下
+        delete(url, start_time, true).unwrap();
+        assert!(kv::history::get(url, start_time).is_err());
+        assert!(!Path::new(&trash_path).exists());
+        assert!(!Path::new(&history.path).exists());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let start = Local
+            .with_ymd_and_hms(2024, 6, 1, 20, 0, 0)
+            .unwrap()
+            .timestamp_millis();
+        let history = |offset: i64, duration: i64, size: u64| {
+            let mut history =
+                RecordingHistory::new("https://live.douyin.com/1", "", PlatformKind::Douyin);
+            history.start_time = start + offset;
+            history.end_time = start + offset + duration;
+            history.file_size = size;
+            history
+        };
+        let histories = vec![
+            history(0, 3_600_000, 100),
+            history(86_400_000, 1_000, 0),
+            history(7 * 86_400_000, 7_200_000, 300),
+        ];
+        let stats = aggregate(histories, start);
+        assert_eq!(stats.total.sessions, 3);
+        assert_eq!(stats.total.bytes, 400);
+        assert_eq!(stats.total.failures, 1);
+        assert_eq!(stats.by_day.len(), 3);
+        // 没有直播间信息时使用录制历史中保存的平台
+        assert_eq!(stats.by_platform.len(), 1);
+        assert_eq!(stats.by_platform[0].key, "Douyin");
+        assert_eq!(stats.by_day[0].key, "2024-06-01");
+        assert_eq!(stats.by_week.len(), 2);
+        assert_eq!(
+            stats.longest[0].end_time - stats.longest[0].start_time,
+            7_200_000
+        );
+    }
+
+    #[test]
+    fn test_rebase() {
+        let from = Path::new("/videos/liveship");
+        let to = Path::new("/mnt/disk/liveship");
+        assert_eq!(
+            rebase(Path::new("/videos/liveship/Douyin/a/1.ts"), from, to),
+            Some(PathBuf::from("/mnt/disk/liveship/Douyin/a/1.ts"))
+        );
+        assert_eq!(rebase(Path::new("/videos/other/1.ts"), from, to), None);
+        assert_eq!(rebase(Path::new("/videos/liveship2/1.ts"), from, to), None);
+    }
+
+    #[test]
+    fn test_parse_file_name() {
+        let expected = Local
+            .with_ymd_and_hms(2024, 6, 1, 20, 0, 0)
+            .unwrap()
+            .timestamp_millis();
+        assert_eq!(parse_filename_time("主播_20240601_200000"), Some(expected));
+        assert_eq!(parse_filename_time("a_b_20240601_200000"), Some(expected));
+        assert_eq!(parse_filename_time("recording"), None);
+        assert_eq!(parse_platform("Douyin"), PlatformKind::Douyin);
+        assert_eq!(
+            parse_platform("MyPlatform"),
+            PlatformKind::Custom("MyPlatform".into())
+        );
+    }
+
+    #[test]
+    fn test_cursor() {
+        let key = (
+            -1700000000000,
+            1700000000000,
+            "https://live.douyin.com/1".to_string(),
+        );
+        assert_eq!(parse_cursor(&format_cursor(&key)).unwrap(), key);
+        assert!(parse_cursor("abc").is_err());
+    }
+}
@@ -1,10 +1,15 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use liveship_lib::{backstage, kv};
+use liveship_lib::{backstage, kv, platform};
 
 fn main() {
-    let _ = kv::init();
+    // 数据库打开或迁移失败时不能继续运行，否则会用旧的数据结构读写
+    if let Err(e) = kv::init() {
+        eprintln!("数据库初始化失败：{:#}", e);
+        std::process::exit(1);
+    }
+    platform::reload();
     backstage::init_with_new_thread();
     liveship_lib::run();
 }
@@ -1,15 +1,22 @@
 use crate::model::AppConfig;
 use crate::model::LiveInfo;
+use crate::model::LiveSample;
+use crate::model::LiveStatus;
 use crate::model::RecordingOption;
 use crate::model::{PlatformKind, Stream};
 use crate::{
-    ffmpeg, kv,
-    model::{JsonMap, JsonValue, RecordStatus, RecordingHistory, RecordingPlan},
-    request, utils,
+    condition, credential, ffmpeg, kv, library,
+    model::{
+        BackupInfo, DbCheckReport, HistoryPage, HistoryQuery, ImportReport, JsonMap, JsonValue,
+        PlanFormat, PlanGroup, PlanPatch, PlanSchedule, RecordStatus, RecordingHistory,
+        RecordingPlan, RecordingStats,
+    },
+    platform, quality, request, schedule, throttle, transfer, utils,
 };
 use dashmap::DashMap;
 use ffmpeg_sidecar::version::ffmpeg_version_with_path;
 use once_cell::sync::Lazy;
+use std::collections::HashMap;
 use std::result::Result;
 use std::{path::PathBuf, process::Child};
 
@@ -29,6 +36,13 @@ pub mod inner {
         live_info: LiveInfo,
         option: Option<RecordingOption>,
     ) -> anyhow::Result<()> {
+        // 搬移录制文件期间不开始新的录制，新文件的路径不会被搬移
+        if let Some(relocation) = kv::relocation::get()? {
+            return Err(anyhow::anyhow!(
+                "Relocation to {} is not finished",
+                relocation.to
+            ));
+        }
         // 如果已经在录制了，就不再录制，返回错误
         if inner::get_record_status(&live_info.url).await? == RecordStatus::Recording {
             return Err(anyhow::anyhow!("Already recording"));
@@ -48,10 +62,31 @@ pub mod inner {
             .to_str()
             .ok_or_else(|| anyhow::anyhow!("Could not convert path to string: {:?}", path))?;
 
-        inner::record_with_ffmpeg(&live_info.url, &stream.url, full_filename, option).await?;
+        // 解析器给出的请求头，加上平台的 User-Agent，否则部分 CDN 会拒绝
+        // 流地址是平台自己的域名时还要带上登录后的 cookie，才能拉到会员或原画的流，CDN 的地址不带
+        let mut headers = stream.headers.clone();
+        let has_header = |headers: &HashMap<String, String>, name: &str| {
+            headers.keys().any(|k| k.eq_ignore_ascii_case(name))
+        };
+        if !has_header(&headers, "user-agent") {
+            let user_agent = kv::config::get()?.user_agent(&live_info.platform_kind);
+            headers.insert("User-Agent".to_string(), user_agent);
+        }
+        if !has_header(&headers, "cookie") {
+            if let Some(cookie) = credential::cookie_for(&live_info.platform_kind, &stream.url) {
+                headers.insert("Cookie".to_string(), cookie);
+            }
+        }
+        inner::record_with_ffmpeg(&live_info.url, &stream.url, full_filename, option, &headers)
+            .await?;
 
         // 记录录制历史
-        let mut history = RecordingHistory::new(&live_info.url, full_filename);
+        let mut history = RecordingHistory::new(
+            &live_info.url,
+            full_filename,
+            live_info.platform_kind.clone(),
+        );
+        history.stream = Some(stream);
         history.live_info = Some(live_info);
         kv::history::add(&history).unwrap_or_else(|e| {
             eprintln!("Could not add recording history: {}", e);
@@ -60,20 +95,61 @@ pub mod inner {
         Ok(())
     }
 
+    /// 录制意外结束后重新开始录制，流地址可能带有过期时间和签名，所以要重新解析直播间拿到新的地址
+    /// 只处理后端有对应解析器的平台，其它平台由前端的轮询重新开始，返回是否重新开始了录制
+    /// 调用前由 backstage 根据计划是否启用和录制策略决定要不要重新开始
+    pub async fn restart_record(previous: &RecordingHistory) -> anyhow::Result<bool> {
+        let (Some(previous_stream), Some(previous_info)) = (&previous.stream, &previous.live_info)
+        else {
+            return Ok(false);
+        };
+        let Some(resolver) = platform::find_resolver(&previous.url) else {
+            return Ok(false);
+        };
+        if resolver.platform_kind() != previous_info.platform_kind {
+            return Ok(false);
+        }
+        // 重新录制需要新的流地址，不使用缓存
+        let live_info = platform::resolve(resolver.as_ref(), &previous.url).await?;
+        if live_info.status != LiveStatus::Live {
+            return Ok(false);
+        }
+        let Some(stream) = select_similar_stream(&live_info.streams, previous_stream) else {
+            return Ok(false);
+        };
+        let option = kv::plan::get_with_defaults(previous.url.clone())?.map(|plan| plan.option);
+        println!("重新开始录制：{}", previous.url);
+        start_record_with_stream(stream, live_info, option).await?;
+        Ok(true)
+    }
+
+    /// 选择和之前协议、清晰度都相同的流，没有的话依次退而求其次：清晰度相同、协议相同、第一个
+    fn select_similar_stream(streams: &[Stream], previous: &Stream) -> Option<Stream> {
+        streams
+            .iter()
+            .find(|s| s.protocol == previous.protocol && s.resolution == previous.resolution)
+            .or_else(|| streams.iter().find(|s| s.resolution == previous.resolution))
+            .or_else(|| streams.iter().find(|s| s.protocol == previous.protocol))
+            .or_else(|| streams.first())
+            .cloned()
+    }
+
     pub(super) async fn record_with_ffmpeg(
         url: &str,
         stream_url: &str,
         full_filename: &str,
         option: Option<RecordingOption>,
+        headers: &HashMap<String, String>,
     ) -> anyhow::Result<()> {
         let ffmpeg_path = kv::config::get()?.ffmpeg_path;
-        let mut child = match ffmpeg::record(&ffmpeg_path, stream_url, full_filename, option) {
-            Ok(child) => child,
-            Err(e) => {
-                eprintln!("Could not start recording: {}", e);
-                return Err(e);
-            }
-        };
+        let mut child =
+            match ffmpeg::record(&ffmpeg_path, stream_url, full_filename, option, headers) {
+                Ok(child) => child,
+                Err(e) => {
+                    eprintln!("Could not start recording: {}", e);
+                    return Err(e);
+                }
+            };
 
         if let Some(status) = child.try_wait()? {
             let output = child.wait_with_output()?;
@@ -112,9 +188,13 @@ pub mod record {
     pub async fn start_record(
         auto_record: bool,
         stream: Stream,
-        live_info: LiveInfo,
+        mut live_info: LiveInfo,
         option: Option<RecordingOption>,
     ) -> Result<RecordStatus, String> {
+        // 短链接展开成直播间网址，避免计划和直播信息以短链接为键
+        live_info.url = platform::canonicalize_url(&live_info.url)
+            .await
+            .map_err(|e| format!("Could not resolve url: {}", e))?;
         // 如果要自动录制，加入录制计划表
         if auto_record {
             let mut plan = RecordingPlan::new(
@@ -140,27 +220,43 @@ pub mod record {
         Ok(RecordStatus::Recording)
     }
 
+    /// 按照计划的选流策略开始录制，用于轮询检查到主播开播时
+    #[tauri::command]
+    pub async fn start_plan_record(live_info: LiveInfo) -> Result<RecordStatus, String> {
+        let plan = kv::plan::get_with_defaults(live_info.url.clone())
+            .map_err(|e| format!("Could not get recording plan: {}", e))?
+            .ok_or_else(|| format!("Recording plan not found: {}", live_info.url))?;
+        let reason = condition::check(&plan.conditions, &live_info)
+            .map_err(|e| format!("Could not check record conditions: {}", e))?;
+        if let Some(reason) = reason {
+            println!("不满足录制条件，跳过：{}，{}", live_info.url, reason);
+            return Ok(RecordStatus::NotRecording);
+        }
+        let stream = quality::select_stream(&live_info.streams, &plan.quality_policy())
+            .ok_or_else(|| format!("No stream found for: {}", live_info.url))?;
+        start_record(false, stream, live_info, Some(plan.option)).await
+    }
+
     /// 停止录制，就是杀死对应 task 的 Child
     #[tauri::command]
     pub async fn stop_record(url: &str) -> Result<RecordStatus, String> {
-        if let Some(mut task) = TASKS.get_mut(url) {
+        // 先从 TASKS 中取出任务，后台检查不会再看到它，也就不会当作意外退出重新开始
+        if let Some((_, mut child)) = TASKS.remove(url) {
             // 杀掉任务
-            task.value_mut().kill().map_err(|e| {
+            child.kill().map_err(|e| {
                 eprintln!("Could not kill task: {}", e);
                 e.to_string()
             })?;
 
             // 杀掉任务后，变成了僵尸进程，通过 wait 来回收资源
-            task.value_mut().wait().map_err(|e| {
+            child.wait().map_err(|e| {
                 eprintln!("Could not wait for task: {}", e);
                 e.to_string()
             })?;
             println!("停止录制成功：{}", url);
         }
-        // 删除对应的 task
-        TASKS.remove(url);
         // 更新录制历史
-        kv::history::end(url).map_err(|e| {
+        library::end_recording(url).map_err(|e| {
             eprintln!("Could not update recording history: {}", e);
             e.to_string()
         })?;
@@ -190,7 +286,7 @@ pub mod record {
                     eprintln!("Could not wait for task: {}", e);
                 }
                 println!("停止录制成功：{}", url);
-                kv::history::end(&url).unwrap_or_else(|e| {
+                library::end_recording(&url).unwrap_or_else(|e| {
                     eprintln!("Could not update recording history: {}", e);
                 });
             }
@@ -201,10 +297,14 @@ pub mod record {
 pub mod plan {
     use super::*;
 
-    /// 获取所有录制计划
+    /// 获取所有录制计划，指定 tag 时只返回这个分组中的计划
     #[tauri::command]
-    pub async fn get_all_plans() -> Result<Vec<RecordingPlan>, String> {
-        let plans = kv::plan::get_all().map_err(|e| {
+    pub async fn get_all_plans(tag: Option<String>) -> Result<Vec<RecordingPlan>, String> {
+        let plans = match &tag {
+            Some(tag) => kv::plan::get_by_tag(tag),
+            None => kv::plan::get_all(),
+        };
+        let plans = plans.map_err(|e| {
             eprintln!("Could not get all recording plans: {}", e);
             e.to_string()
         })?;
@@ -223,7 +323,19 @@ pub mod plan {
 
     /// 新增录制计划
     #[tauri::command]
-    pub async fn add_plan(plan: RecordingPlan) -> Result<(), String> {
+    pub async fn add_plan(mut plan: RecordingPlan) -> Result<(), String> {
+        plan.url = platform::canonicalize_url(&plan.url)
+            .await
+            .map_err(|e| format!("Could not resolve url: {}", e))?;
+        if let Some(live_info) = plan.live_info.as_mut() {
+            live_info.url = plan.url.clone();
+        }
+        platform::check_supported(&plan.url)
+            .await
+            .map_err(|e| format!("Could not add recording plan: {}", e))?;
+        // 策略、选项和录制条件都要有效，和修改计划时的检查一致
+        plan.validate()
+            .map_err(|e| format!("Invalid recording plan: {}", e))?;
         kv::plan::add(&plan).map_err(|e| {
             eprintln!("Could not add recording plan: {}", e);
             e.to_string()
@@ -231,13 +343,26 @@ pub mod plan {
         Ok(())
     }
 
+    /// 通过网址新增录制计划，display_name 用于直接流地址这类没有主播名的来源
     #[tauri::command]
-    pub async fn add_plan_with_url(url: String) -> Result<(), String> {
+    pub async fn add_plan_with_url(
+        url: String,
+        display_name: Option<String>,
+    ) -> Result<(), String> {
         let platform_kind = PlatformKind::from(url.clone());
         if platform_kind == PlatformKind::Unknown {
             return Err("Unknown platform".to_string());
         }
-        let plan = RecordingPlan::new_with_url(&url);
+        let url = platform::canonicalize_url(&url)
+            .await
+            .map_err(|e| format!("Could not resolve url: {}", e))?;
+        platform::check_supported(&url)
+            .await
+            .map_err(|e| format!("Could not add recording plan: {}", e))?;
+        let mut plan = RecordingPlan::new_with_url(&url);
+        plan.display_name = display_name;
+        plan.validate()
+            .map_err(|e| format!("Invalid recording plan: {}", e))?;
         kv::plan::add(&plan).map_err(|e| {
             eprintln!("Could not add recording plan: {}", e);
             e.to_string()
@@ -255,6 +380,109 @@ pub mod plan {
         Ok(())
     }
 
+    /// 按字段修改录制计划，保留 created_at
+    /// 正在录制时，新的设置从下一场开始生效；restart 为 true 时停止当前录制，由轮询用新的设置重新开始
+    #[tauri::command]
+    pub async fn update_plan(
+        url: String,
+        patch: PlanPatch,
+        restart: bool,
+    ) -> Result<RecordingPlan, String> {
+        let plan = kv::plan::update(&url, |plan| {
+            plan.apply(patch);
+            plan.validate()
+        })
+        .map_err(|e| format!("Could not update recording plan: {}", e))?;
+        if restart && TASKS.contains_key(&url) {
+            record::stop_record(&url).await?;
+            schedule::check_now(&url);
+        }
+        Ok(plan)
+    }
+
+    /// 按字段修改分组中的所有计划，可以用来批量启用、停用或修改选项
+    #[tauri::command]
+    pub async fn update_group_plans(
+        tag: String,
+        patch: PlanPatch,
+        restart: bool,
+    ) -> Result<Vec<RecordingPlan>, String> {
+        // 所有计划在一个事务中修改，有一个无效就都不修改
+        let plans = kv::plan::update_by_tag(&tag, |plan| {
+            plan.apply(patch.clone());
+            plan.validate()
+        })
+        .map_err(|e| format!("Could not update recording plans: {}", e))?;
+        for plan in &plans {
+            if restart && TASKS.contains_key(&plan.url) {
+                record::stop_record(&plan.url).await?;
+                schedule::check_now(&plan.url);
+            }
+        }
+        Ok(plans)
+    }
+
+    /// 删除分组中的所有计划
+    #[tauri::command]
+    pub async fn delete_group_plans(tag: String) -> Result<(), String> {
+        let plans = kv::plan::get_by_tag(&tag)
+            .map_err(|e| format!("Could not get recording plans: {}", e))?;
+        for plan in plans {
+            delete_plan(&plan.url).await?;
+        }
+        Ok(())
+    }
+
+    /// 获取所有分组，包括只出现在计划标签中、还没有默认设置的分组
+    #[tauri::command]
+    pub async fn get_plan_groups() -> Result<Vec<PlanGroup>, String> {
+        let mut groups =
+            kv::group::get_all().map_err(|e| format!("Could not get plan groups: {}", e))?;
+        let plans = kv::plan::get_all().map_err(|e| format!("Could not get plans: {}", e))?;
+        for tag in plans.iter().flat_map(|plan| &plan.tags) {
+            if !groups.iter().any(|g| g.name == *tag) {
+                groups.push(PlanGroup {
+                    name: tag.clone(),
+                    ..Default::default()
+                });
+            }
+        }
+        Ok(groups)
+    }
+
+    /// 保存分组的默认设置
+    #[tauri::command]
+    pub async fn set_plan_group(group: PlanGroup) -> Result<(), String> {
+        if group.name.trim().is_empty() {
+            return Err("Group name is empty".into());
+        }
+        kv::group::set(&group).map_err(|e| format!("Could not set plan group: {}", e))
+    }
+
+    /// 删除分组的默认设置，计划上的标签保留
+    #[tauri::command]
+    pub async fn delete_plan_group(name: String) -> Result<(), String> {
+        kv::group::delete(&name).map_err(|e| format!("Could not delete plan group: {}", e))
+    }
+
+    /// 批量导入录制计划，dry_run 为 true 时只检查每一行，不保存
+    #[tauri::command]
+    pub async fn import_plans(
+        content: String,
+        format: PlanFormat,
+        dry_run: bool,
+    ) -> Result<ImportReport, String> {
+        transfer::import(&content, format, dry_run)
+            .await
+            .map_err(|e| format!("Could not import recording plans: {}", e))
+    }
+
+    /// 按格式导出所有录制计划
+    #[tauri::command]
+    pub async fn export_plans(format: PlanFormat) -> Result<String, String> {
+        transfer::export(format).map_err(|e| format!("Could not export recording plans: {}", e))
+    }
+
     /// 更新录制计划状态
     #[tauri::command]
     pub async fn update_plan_status(url: &str, enabled: bool) -> Result<(), String> {
@@ -290,6 +518,26 @@ pub mod plan {
         }
         result
     }
+
+    /// 获取未在录制中、并且到了检查时间的计划，检查间隔根据主播以往的开播时间调整
+    #[tauri::command]
+    pub async fn get_plans_to_check() -> Result<Vec<RecordingPlan>, String> {
+        let plans = get_plans_not_recording().await;
+        let plans =
+            schedule::due_plans(plans).map_err(|e| format!("Could not schedule plans: {}", e))?;
+        Ok(plans)
+    }
+
+    /// 获取所有计划的检查安排和预测的开播时间
+    #[tauri::command]
+    pub async fn get_plan_schedules() -> Result<Vec<PlanSchedule>, String> {
+        let plans = kv::plan::get_all().map_err(|e| format!("Could not get plans: {}", e))?;
+        plans
+            .iter()
+            .map(|plan| schedule::plan_schedule(&plan.url))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(|e| format!("Could not get plan schedules: {}", e))
+    }
 }
 
 pub mod query_history {
@@ -327,39 +575,133 @@ pub mod query_history {
 
 pub mod history {
     use super::*;
+    use crate::model::{RelocateProgress, Relocation, ScanReport};
+    use tauri::{AppHandle, Emitter};
 
     /// 获取所有录制历史
     #[tauri::command]
     pub async fn get_all_history() -> Result<Vec<RecordingHistory>, String> {
         let mut histories = kv::history::get_all()
             .map_err(|e| format!("Could not get all recording histories: {}", e))?;
+        // 回收站中的录制通过 query_history 查询
+        histories.retain(|history| history.trash.is_none());
         // 遍历列表，计算每个文件的尺寸，更新到 histories 中，仅在文件存在时才计算
         for history in &mut histories {
-            if std::path::Path::new(&history.path).exists() {
-                let file_size = std::fs::metadata(&history.path)
-                    .map_err(|e| format!("Could not get file size: {}", e))?
-                    .len();
-                history.file_size = file_size;
-            } else {
-                // 如果文件不存在，我们认为文件已经被删除
-                history.deleted = true;
-            }
+            library::fill_file_info(history)
+                .map_err(|e| format!("Could not get file size: {}", e))?;
         }
         Ok(histories)
     }
 
-    /// 删除一条历史记录
+    /// 统计开始时间在范围内的录制：按主播、平台、日期和周的时长、字节数和失败次数
+    #[tauri::command]
+    pub async fn get_recording_stats(
+        start_from: Option<i64>,
+        start_to: Option<i64>,
+    ) -> Result<RecordingStats, String> {
+        library::stats(start_from, start_to)
+            .map_err(|e| format!("Could not get recording stats: {}", e))
+    }
+
+    /// 获取一次录制过程中记录的观看人数、标题和封面，按时间排列
+    ///
+    /// 采样只在后端检查录制中的直播间时记录，前端解析的平台不会在录制中轮询，没有采样，返回空列表
+    #[tauri::command]
+    pub async fn get_live_samples(url: String, start_time: i64) -> Result<Vec<LiveSample>, String> {
+        kv::sample::get(&url, start_time).map_err(|e| format!("Could not get live samples: {}", e))
+    }
+
+    /// 按条件分页查询录制历史，返回总条数和下一页的游标
+    #[tauri::command]
+    pub async fn query_history(query: HistoryQuery) -> Result<HistoryPage, String> {
+        library::query(&query).map_err(|e| format!("Could not query recording histories: {}", e))
+    }
+
+    /// 删除一条历史记录，delete_file 为 true 时把文件移到回收站
     #[tauri::command]
     pub async fn delete_history(
         url: &str,
         start_time: i64,
         delete_file: bool,
     ) -> Result<(), String> {
-        kv::history::delete(url, start_time, delete_file)
+        library::delete(url, start_time, delete_file)
             .map_err(|e| format!("Could not delete recording history: {}", e))?;
         Ok(())
     }
 
+    /// 批量删除历史记录，每一项是 (url, start_time)，遇到错误时继续删除其它的，返回删除失败的项
+    #[tauri::command]
+    pub async fn delete_histories(
+        histories: Vec<(String, i64)>,
+        delete_file: bool,
+    ) -> Result<Vec<(String, i64)>, String> {
+        let mut failed = vec![];
+        for (url, start_time) in histories {
+            if let Err(e) = library::delete(&url, start_time, delete_file) {
+                eprintln!("Could not delete recording history {}: {}", url, e);
+                failed.push((url, start_time));
+            }
+        }
+        Ok(failed)
+    }
+
+    /// 把录制文件搬到新的保存路径，完成后更新录制历史的路径和配置中的保存路径
+    /// 进度通过 library-relocate-progress 事件发送，录制中不能搬移
+    #[tauri::command]
+    pub async fn relocate_library(app: AppHandle, to: String, copy: bool) -> Result<(), String> {
+        if !TASKS.is_empty() {
+            return Err("Could not relocate library while recording".into());
+        }
+        tokio::task::spawn_blocking(move || {
+            library::relocate(&to, copy, |progress| emit_relocate_progress(&app, progress))
+        })
+        .await
+        .map_err(|e| format!("Could not relocate library: {}", e))?
+        .map_err(|e| format!("Could not relocate library: {}", e))
+    }
+
+    /// 继续上次中断的搬移
+    #[tauri::command]
+    pub async fn resume_relocation(app: AppHandle) -> Result<(), String> {
+        if !TASKS.is_empty() {
+            return Err("Could not relocate library while recording".into());
+        }
+        tokio::task::spawn_blocking(move || {
+            library::resume_relocation(|progress| emit_relocate_progress(&app, progress))
+        })
+        .await
+        .map_err(|e| format!("Could not resume relocation: {}", e))?
+        .map_err(|e| format!("Could not resume relocation: {}", e))
+    }
+
+    /// 扫描保存路径，找回被移动过的录制文件，导入不在录制历史中的录制文件
+    #[tauri::command]
+    pub async fn rescan_library(dry_run: bool) -> Result<ScanReport, String> {
+        tokio::task::spawn_blocking(move || library::rescan(dry_run))
+            .await
+            .map_err(|e| format!("Could not rescan library: {}", e))?
+            .map_err(|e| format!("Could not rescan library: {}", e))
+    }
+
+    /// 获取没有完成的搬移任务
+    #[tauri::command]
+    pub async fn get_relocation() -> Result<Option<Relocation>, String> {
+        kv::relocation::get().map_err(|e| format!("Could not get relocation: {}", e))
+    }
+
+    fn emit_relocate_progress(app: &AppHandle, progress: RelocateProgress) {
+        if let Err(e) = app.emit("library-relocate-progress", progress) {
+            eprintln!("Could not emit relocate progress: {}", e);
+        }
+    }
+
+    /// 把回收站中的录制恢复到原来的位置
+    #[tauri::command]
+    pub async fn restore_history(url: &str, start_time: i64) -> Result<(), String> {
+        library::restore(url, start_time)
+            .map_err(|e| format!("Could not restore recording history: {}", e))
+    }
+
     /// 在文件管理器中打开文件夹
     #[tauri::command]
     pub async fn open_in_folder(path: &str) -> Result<(), String> {
@@ -367,6 +709,46 @@ pub mod history {
     }
 }
 
+pub mod database {
+    use super::*;
+
+    /// 检查数据库中互相对不上的数据，不做修改
+    #[tauri::command]
+    pub async fn db_check() -> Result<DbCheckReport, String> {
+        tokio::task::spawn_blocking(|| kv::maintenance::check(|url| TASKS.contains_key(url), false))
+            .await
+            .map_err(|e| format!("Could not check database: {}", e))?
+            .map_err(|e| format!("Could not check database: {}", e))
+    }
+
+    /// 检查并修复数据库，数据库文件在下次启动时压缩
+    #[tauri::command]
+    pub async fn db_repair() -> Result<DbCheckReport, String> {
+        tokio::task::spawn_blocking(|| kv::maintenance::check(|url| TASKS.contains_key(url), true))
+            .await
+            .map_err(|e| format!("Could not repair database: {}", e))?
+            .map_err(|e| format!("Could not repair database: {}", e))
+    }
+
+    /// 立即创建一个数据库备份
+    #[tauri::command]
+    pub async fn create_backup() -> Result<BackupInfo, String> {
+        kv::backup::create().map_err(|e| format!("Could not create backup: {}", e))
+    }
+
+    /// 获取所有数据库备份，最新的在前
+    #[tauri::command]
+    pub async fn get_backups() -> Result<Vec<BackupInfo>, String> {
+        kv::backup::list().map_err(|e| format!("Could not get backups: {}", e))
+    }
+
+    /// 检查备份后登记恢复，重新启动后生效
+    #[tauri::command]
+    pub async fn restore_backup(name: String) -> Result<(), String> {
+        kv::backup::restore(&name).map_err(|e| format!("Could not restore backup: {}", e))
+    }
+}
+
 pub mod config {
     use super::*;
 
@@ -381,6 +763,8 @@ pub mod config {
     #[tauri::command]
     pub fn set_config(config: AppConfig) -> Result<(), String> {
         kv::config::set(&config).map_err(|e| format!("Could not set config: {}", e))?;
+        // 外部解析器的路径可能变了
+        platform::external::reload();
         Ok(())
     }
 }
@@ -540,6 +924,152 @@ pub mod request_api {
     }
 }
 
+pub mod platform_api {
+    use crate::model::CustomPlatform;
+
+    use super::*;
+
+    /// 通过后端的解析器获取直播间信息，用于前端没有实现的平台，例如自定义平台
+    #[tauri::command]
+    pub async fn get_live_info(url: String) -> Result<LiveInfo, String> {
+        let live_info = platform::get_live_info(&url)
+            .await
+            .map_err(|e| format!("Could not get live info: {}", e))?;
+        Ok(live_info)
+    }
+
+    /// 前端的解析器通过后端请求平台接口，后端会带上保存的账号 cookie
+    #[tauri::command]
+    pub async fn request_for_platform(
+        platform_kind: PlatformKind,
+        url: String,
+        headers: HashMap<String, String>,
+        body: Option<String>,
+    ) -> Result<String, String> {
+        let text = platform::request_text(&platform_kind, &url, &headers, body)
+            .await
+            .map_err(|e| format!("Could not request: {}", e))?;
+        Ok(text)
+    }
+
+    /// 获取缓存的直播间信息，用于界面展示，缓存在有效期内时不请求网络
+    /// 缓存过期时通过后端的解析器重新获取，没有后端解析器的平台返回过期的缓存，没有缓存时返回 None
+    #[tauri::command]
+    pub async fn get_cached_live_info(url: String) -> Result<Option<LiveInfo>, String> {
+        if let Some(live_info) = platform::cached_live_info(&url)
+            .map_err(|e| format!("Could not get cached live info: {}", e))?
+        {
+            return Ok(Some(live_info));
+        }
+        // 外部解析器能处理任意网址，只使用网址所属平台自己的解析器
+        let resolvable = platform::find_resolver(&url)
+            .is_some_and(|resolver| resolver.platform_kind() == PlatformKind::from(&url));
+        if resolvable {
+            let live_info = platform::get_live_info(&url)
+                .await
+                .map_err(|e| format!("Could not get live info: {}", e))?;
+            return Ok(Some(live_info));
+        }
+        let live_info =
+            kv::live::get(&url).map_err(|e| format!("Could not get cached live info: {}", e))?;
+        Ok(live_info)
+    }
+
+    /// 获取所有自定义平台，包括定义文件和数据库中的
+    #[tauri::command]
+    pub async fn get_custom_platforms() -> Result<Vec<CustomPlatform>, String> {
+        let platforms = platform::custom::load_all()
+            .map_err(|e| format!("Could not get custom platforms: {}", e))?;
+        Ok(platforms)
+    }
+
+    /// 新增或修改自定义平台，保存到数据库
+    #[tauri::command]
+    pub async fn add_custom_platform(platform: CustomPlatform) -> Result<(), String> {
+        platform::custom::validate(&platform)
+            .map_err(|e| format!("Invalid custom platform: {}", e))?;
+        kv::custom_platform::add(&platform)
+            .map_err(|e| format!("Could not add custom platform: {}", e))?;
+        platform::custom::reload();
+        Ok(())
+    }
+
+    /// 删除数据库中的自定义平台，定义文件需要手动删除
+    #[tauri::command]
+    pub async fn delete_custom_platform(name: String) -> Result<(), String> {
+        kv::custom_platform::delete(&name)
+            .map_err(|e| format!("Could not delete custom platform: {}", e))?;
+        platform::custom::reload();
+        Ok(())
+    }
+
+    /// 重新加载定义文件
+    #[tauri::command]
+    pub async fn reload_custom_platforms() -> Result<(), String> {
+        platform::custom::reload();
+        Ok(())
+    }
+
+    /// 检查 streamlink 或 yt-dlp 是否可用，返回版本号
+    #[tauri::command]
+    pub async fn check_external_resolver_version(path: String) -> Result<String, String> {
+        let version = platform::external::version(&path)
+            .map_err(|e| format!("Could not check external resolver: {}", e))?;
+        Ok(version)
+    }
+}
+
+pub mod credential_api {
+    use crate::model::{CredentialStatus, PlatformCredential};
+
+    use super::*;
+
+    /// 保存平台的 cookie，cookie 保存在系统钥匙串中
+    /// 外部解析器需要传入 cookie 所属的域名，其它平台不需要
+    #[tauri::command]
+    pub async fn set_platform_cookie(
+        platform_kind: PlatformKind,
+        host: Option<String>,
+        cookies: String,
+    ) -> Result<PlatformCredential, String> {
+        let credential =
+            credential::set_cookie(&platform_kind, &host.unwrap_or_default(), &cookies)
+                .map_err(|e| format!("Could not set cookie: {}", e))?;
+        Ok(credential)
+    }
+
+    /// 获取所有平台的凭据信息，不包含 cookie 本身
+    #[tauri::command]
+    pub async fn get_platform_credentials() -> Result<Vec<PlatformCredential>, String> {
+        let credentials =
+            credential::get_all().map_err(|e| format!("Could not get credentials: {}", e))?;
+        Ok(credentials)
+    }
+
+    /// 删除平台的 cookie
+    #[tauri::command]
+    pub async fn delete_platform_cookie(
+        platform_kind: PlatformKind,
+        host: Option<String>,
+    ) -> Result<(), String> {
+        credential::delete_cookie(&platform_kind, &host.unwrap_or_default())
+            .map_err(|e| format!("Could not delete cookie: {}", e))?;
+        Ok(())
+    }
+
+    /// 校验平台的 cookie 是否还有效
+    #[tauri::command]
+    pub async fn validate_platform_cookie(
+        platform_kind: PlatformKind,
+        host: Option<String>,
+    ) -> Result<CredentialStatus, String> {
+        let status = credential::validate(&platform_kind, &host.unwrap_or_default())
+            .await
+            .map_err(|e| format!("Could not validate cookie: {}", e))?;
+        Ok(status)
+    }
+}
+
 pub mod my_utils {
     use rusty_ytdl::{Video, VideoInfo, VideoOptions};
 
@@ -570,10 +1100,12 @@ pub mod my_utils {
         }
         let video = Video::new_with_options(&url, video_options)
             .map_err(|e| format!("Could not get video info: {}", e))?;
-        let video_info = video
-            .get_info()
-            .await
-            .map_err(|e| format!("Could not get video info: {}", e))?;
+        // 前端轮询 YouTube 计划时也会调用这里，和其它平台一样限流
+        let video_info = throttle::run(&PlatformKind::Youtube, || async {
+            video.get_info().await.map_err(|e| anyhow::anyhow!("{}", e))
+        })
+        .await
+        .map_err(|e| format!("Could not get video info: {}", e))?;
         Ok(video_info)
     }
 
@@ -1,6 +1,7 @@
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use strum::Display;
+use std::collections::HashMap;
+use strum::{Display, EnumIter, IntoEnumIterator};
 
 pub type JsonValue = serde_json::Value;
 pub type JsonMap = serde_json::Map<String, JsonValue>;
@@ -32,6 +33,22 @@ pub struct LiveInfo {
     pub streams: Vec<Stream>,
     // 直播平台
     pub platform_kind: PlatformKind,
+    // 直播分区或游戏分类，平台没有提供时为空
+    #[serde(default)]
+    pub category: String,
+    // 后端解析器获取这条信息的时间，毫秒时间戳，用于缓存过期判断，0 表示不是后端获取的
+    #[serde(default)]
+    pub updated_at: i64,
+}
+
+// 主播，写入直播间信息时更新，用于按主播查询
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Anchor {
+    pub url: String,
+    pub anchor_name: String,
+    pub anchor_avatar: String,
+    pub platform_kind: PlatformKind,
 }
 
 // 存储设置，用来指明保存位置，文件名等信息
@@ -50,6 +67,47 @@ pub struct Stream {
     pub url: String,
     pub resolution: String,
     pub protocol: StreamingProtocol,
+    // 解析器明确知道的清晰度信息，没有时从 resolution 名称推断
+    #[serde(default)]
+    pub quality: Option<StreamQuality>,
+    // 拉流时需要带上的请求头，例如 Referer、Origin，部分 CDN 没有这些会拒绝请求
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+// 归一化的清晰度，不同平台的清晰度名称各不相同，统一成高度、码率和是否原画
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StreamQuality {
+    // 视频高度，例如 1080
+    pub height: Option<u32>,
+    // 码率，单位 kbps
+    pub bitrate: Option<u32>,
+    // 是否是原画
+    pub origin: bool,
+}
+
+// 选择流的策略
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QualityPolicy {
+    pub preference: QualityPreference,
+    // 协议偏好，按顺序尝试，例如 [Flv, Hls] 表示优先 flv，没有再用 hls，为空表示不限制
+    #[serde(default)]
+    pub protocols: Vec<StreamingProtocol>,
+}
+
+// 清晰度偏好
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum QualityPreference {
+    // 最高清晰度，原画优先
+    Highest,
+    // 最低清晰度
+    Lowest,
+    // 不超过指定的高度，例如 1080，都超过时选择最低的
+    AtMost(u32),
+    // 指定清晰度名称，和旧版计划里的 stream_resolution 一样，找不到时选择最高清晰度
+    Label(String),
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
@@ -117,7 +175,7 @@ pub enum RecordStatus {
 }
 
 // 直播平台类型
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Display)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Display, EnumIter)]
 pub enum PlatformKind {
     Douyin,
     Tiktok,
@@ -128,6 +186,13 @@ pub enum PlatformKind {
     Douyu,
     Twitch,
     Youtube,
+    // 直接的流地址，例如 .flv、.m3u8 或者 rtmp 地址
+    Direct,
+    // 内置平台和自定义平台都不支持，通过 streamlink 或 yt-dlp 解析的网站
+    External,
+    // 用户自定义的平台，值是定义里的平台名
+    #[strum(to_string = "{0}")]
+    Custom(String),
     Unknown,
 }
 
@@ -144,6 +209,18 @@ pub struct RecordingHistory {
     pub end_time: i64,
     // 保存路径
     pub path: String,
+    // 录制时使用的流，重新开始录制时按照它的协议和清晰度选择新的流
+    #[serde(default)]
+    pub stream: Option<Stream>,
+    // 移到回收站时的信息，不在回收站中时为 None
+    #[serde(default)]
+    pub trash: Option<TrashInfo>,
+    // 文件尺寸加开头和结尾的哈希，扫描时计算，文件被移动后用来找到它
+    #[serde(default)]
+    pub fingerprint: String,
+    // 录制的平台，开始录制时确定，之后自定义平台或外部解析器的配置变化也不会影响它
+    #[serde(default)]
+    pub platform_kind: Option<PlatformKind>,
     // 以下字段不保存到数据库，也不从数据库读取，每次都重新计算
     // 文件尺寸
     pub file_size: u64,
@@ -153,6 +230,177 @@ pub struct RecordingHistory {
     pub live_info: Option<LiveInfo>,
 }
 
+// 回收站中的录制文件
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashInfo {
+    // 文件在回收站中的路径，恢复时移回录制历史的 path
+    pub path: String,
+    // 移到回收站的时间，毫秒时间戳
+    pub trashed_at: i64,
+}
+
+// 扫描保存路径的结果，dry_run 为 true 时没有写入数据库
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanReport {
+    pub dry_run: bool,
+    // 扫描到的录制文件数
+    pub scanned: usize,
+    // 文件被移动过，已经更新路径的录制历史
+    pub relinked: Vec<RecordingHistory>,
+    // 导入的新录制历史
+    pub imported: Vec<RecordingHistory>,
+    // 文件不在了，也没有找到的录制历史
+    pub missing: Vec<RecordingHistory>,
+    // 没有导入的文件，例如已知录制转换出的 mp4
+    pub skipped: Vec<String>,
+}
+
+// 搬移录制文件的任务，完成前保存在数据库中，中断后可以继续
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Relocation {
+    // 原来的保存路径
+    pub from: String,
+    // 新的保存路径，完成后写入配置
+    pub to: String,
+    // 为 true 时复制文件，保留原来的文件
+    pub copy: bool,
+}
+
+// 搬移录制文件的进度，通过 library-relocate-progress 事件发送
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelocateProgress {
+    pub total: usize,
+    pub done: usize,
+    // 正在处理的文件，全部完成时为空
+    pub path: String,
+}
+
+// 录制历史的查询条件，为空的条件不做限制
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HistoryQuery {
+    pub platform_kind: Option<PlatformKind>,
+    // 主播名，需要完全一致
+    pub anchor_name: Option<String>,
+    // 开始时间的范围，毫秒时间戳，包含 start_from，不包含 start_to
+    pub start_from: Option<i64>,
+    pub start_to: Option<i64>,
+    pub status: Option<RecordStatus>,
+    // 最小文件尺寸，单位字节
+    pub min_size: Option<u64>,
+    // 标题包含的文字，不区分大小写
+    pub title: Option<String>,
+    // 为 true 时只查询回收站中的录制，否则不包括回收站中的
+    pub trashed: bool,
+    pub sort: HistorySort,
+    // 上一页返回的 next_cursor，为空时从第一页开始
+    pub cursor: Option<String>,
+    // 每页条数，0 表示使用默认值
+    pub limit: usize,
+}
+
+// 录制历史的排序方式
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub enum HistorySort {
+    #[default]
+    StartTimeDesc,
+    StartTimeAsc,
+    DurationDesc,
+}
+
+// 一页录制历史
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryPage {
+    pub items: Vec<RecordingHistory>,
+    // 满足条件的总条数
+    pub total: usize,
+    // 下一页的游标，没有下一页时为空
+    pub next_cursor: Option<String>,
+}
+
+// 录制过程中定期记录的直播间状态
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveSample {
+    // 记录时间，毫秒时间戳
+    pub time: i64,
+    // 平台给出的观看人数
+    pub viewer_count: String,
+    // 解析成数字的观看人数，无法解析时为空
+    pub viewers: Option<u64>,
+    pub title: String,
+    pub room_cover: String,
+}
+
+// 录制统计
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingStats {
+    pub total: StatsBucket,
+    pub by_anchor: Vec<StatsBucket>,
+    pub by_platform: Vec<StatsBucket>,
+    // 按开始录制的日期，本地时间，键是 2024-06-01，按时间排列
+    pub by_day: Vec<StatsBucket>,
+    // 按 ISO 周，键是 2024-W22，按时间排列
+    pub by_week: Vec<StatsBucket>,
+    // 时长最长的录制
+    pub longest: Vec<RecordingHistory>,
+}
+
+// 一组录制的统计
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsBucket {
+    // 分组的键，例如主播名、平台或日期
+    pub key: String,
+    // 录制次数
+    pub sessions: u64,
+    // 总时长，单位毫秒
+    pub duration: i64,
+    // 总字节数，已删除的文件不计算
+    pub bytes: u64,
+    // 很快就退出的录制次数
+    pub failures: u64,
+    // 平均时长，单位毫秒
+    pub average_duration: i64,
+}
+
+// 数据库检查的结果，repaired 为 true 时列出的问题已经修复
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DbCheckReport {
+    // 有正在录制的标记，但是没有录制任务的 url
+    pub orphaned_recordings: Vec<String>,
+    // 录制文件已经不存在的录制历史，只报告，修复时不删除
+    pub missing_files: Vec<RecordingHistory>,
+    // 既没有计划也没有录制历史的直播间 url
+    pub orphaned_lives: Vec<String>,
+    // 录制历史已经不存在的观看人数记录条数
+    pub orphaned_samples: u64,
+    // 和录制历史对不上的索引条目数
+    pub broken_index_entries: u64,
+    pub repaired: bool,
+    // 下次启动时压缩数据库文件
+    pub compact_scheduled: bool,
+}
+
+// 一个数据库备份文件
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    // 文件名，恢复时用它指定备份
+    pub name: String,
+    pub path: String,
+    pub size: u64,
+    // 备份时间，毫秒时间戳
+    pub created_at: i64,
+}
+
 // 录制计划
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -172,11 +420,127 @@ pub struct RecordingPlan {
     // 录制选项
     #[serde(default)]
     pub option: RecordingOption,
+    // 用户指定的显示名称，直接流地址没有主播名，用它代替
+    #[serde(default)]
+    pub display_name: Option<String>,
+    // 选择流的策略，为空时使用 stream_protocol 和 stream_resolution
+    #[serde(default)]
+    pub quality_policy: Option<QualityPolicy>,
+    // 录制条件，开播时和录制中标题变化时检查，不满足时不录制
+    #[serde(default)]
+    pub conditions: RecordCondition,
+    // 所属的分组，一个计划可以属于多个分组
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
-// 录制选项
+// 计划分组的默认设置，分组中的计划没有自己设置时使用
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 #[serde(rename_all = "camelCase", default)]
+pub struct PlanGroup {
+    pub name: String,
+    // 计划的录制选项是默认值时使用
+    pub option: Option<RecordingOption>,
+    // 计划没有选流策略时使用
+    pub quality_policy: Option<QualityPolicy>,
+}
+
+// 修改录制计划时提交的字段，为空的字段保持不变
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PlanPatch {
+    pub strategy: Option<RecordingStrategy>,
+    pub stream_protocol: Option<StreamingProtocol>,
+    pub stream_resolution: Option<String>,
+    pub enabled: Option<bool>,
+    pub option: Option<RecordingOption>,
+    // 空字符串表示清除显示名称
+    pub display_name: Option<String>,
+    // null 表示清除，改回使用分组或默认的选流策略
+    #[serde(deserialize_with = "deserialize_nullable")]
+    pub quality_policy: Option<Option<QualityPolicy>>,
+    pub conditions: Option<RecordCondition>,
+    pub tags: Option<Vec<String>>,
+}
+
+// 区分没有提交的字段和提交的 null：没有提交时是 None，null 是 Some(None)
+fn deserialize_nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// 批量导入导出计划的格式
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum PlanFormat {
+    // 每行一个网址，# 开头的行是注释
+    Text,
+    // 第一行可以是表头，列见 transfer::CSV_COLUMNS
+    Csv,
+    // Vec<RecordingPlan> 的 json
+    Json,
+    // DouyinLiveRecorder 的 URL_config.ini
+    UrlConfig,
+}
+
+// 导入计划的结果
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    // 是否只是检查，没有保存
+    pub dry_run: bool,
+    pub entries: Vec<ImportEntry>,
+}
+
+// 导入文件中每一条计划的结果
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportEntry {
+    // 行号，从 1 开始，json 格式是数组下标加 1
+    pub line: usize,
+    pub url: String,
+    pub result: ImportResult,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ImportResult {
+    // 已导入，dry_run 时表示可以导入
+    Added,
+    // 已经有这个网址的计划，或者文件中前面出现过
+    Duplicate,
+    Error(String),
+}
+
+// 计划的检查安排，根据主播以往的开播时间计算
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanSchedule {
+    pub url: String,
+    // 下一次检查是否开播的时间，毫秒时间戳
+    pub next_check_at: i64,
+    // 预测的下一次开播时间，毫秒时间戳，没有录制历史时为空
+    pub predicted_live_at: Option<i64>,
+}
+
+// 录制条件，未设置的条件不做限制
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RecordCondition {
+    // 标题需要匹配的正则，不区分大小写
+    pub title_include: Option<String>,
+    // 标题匹配这个正则时不录制
+    pub title_exclude: Option<String>,
+    // 分区需要包含的文字
+    pub category: Option<String>,
+    // 最少观看人数
+    pub min_viewer_count: Option<u64>,
+}
+
+// 录制选项
+#[derive(Debug, Clone, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
 pub struct RecordingOption {
     // 使用代理
     pub use_proxy: Option<String>,
@@ -210,6 +574,55 @@ pub struct AppConfig {
     pub save_path: String,
     // 开播信息检查间隔，单位秒
     pub live_info_check_interval: u64,
+    // streamlink 可执行文件路径，为空时不使用
+    #[serde(default)]
+    pub streamlink_path: String,
+    // yt-dlp 可执行文件路径，为空时不使用，streamlink 和 yt-dlp 都配置时优先使用 streamlink
+    #[serde(default)]
+    pub ytdlp_path: String,
+    // 每个平台录制时使用的 User-Agent，键是平台名，没有设置的平台使用 Chrome
+    #[serde(default)]
+    pub user_agent_profiles: HashMap<String, UserAgentProfile>,
+    // 每个平台同时进行的解析请求数
+    #[serde(default = "config::default_resolver_concurrency")]
+    pub resolver_concurrency: usize,
+    // 同一平台两次解析请求的最小间隔，单位毫秒
+    #[serde(default = "config::default_resolver_min_interval")]
+    pub resolver_min_interval: u64,
+    // 直播信息缓存的有效期，单位秒，有效期内不会重复请求平台
+    #[serde(default = "config::default_live_info_cache_ttl")]
+    pub live_info_cache_ttl: u64,
+    // 检查开播的最短间隔，单位秒，用于预测的开播时间附近
+    #[serde(default = "config::default_poll_min_interval")]
+    pub poll_min_interval: u64,
+    // 检查开播的最长间隔，单位秒，用于离预测的开播时间很远时
+    #[serde(default = "config::default_poll_max_interval")]
+    pub poll_max_interval: u64,
+    // 数据库备份目录，为空时使用配置目录下的 backups
+    #[serde(default)]
+    pub backup_dir: String,
+    // 保留的数据库备份个数，超过时删除最旧的
+    #[serde(default = "config::default_backup_count")]
+    pub backup_count: usize,
+    // 自动备份数据库的间隔，单位小时，为 0 时不自动备份
+    #[serde(default = "config::default_backup_interval")]
+    pub backup_interval: u64,
+    // 回收站中的录制保留的天数，超过后彻底删除，为 0 时不自动删除
+    #[serde(default = "config::default_trash_retention_days")]
+    pub trash_retention_days: u64,
+}
+
+// 录制时使用的 User-Agent
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Default)]
+pub enum UserAgentProfile {
+    #[default]
+    Chrome,
+    Firefox,
+    Safari,
+    // 手机浏览器，部分平台手机端的流限制更少
+    Mobile,
+    // 自定义的 User-Agent
+    Custom(String),
 }
 
 pub mod config {
@@ -229,6 +642,73 @@ pub mod config {
                 ffmpeg_path: "ffmpeg".into(),
                 save_path,
                 live_info_check_interval: 60,
+                streamlink_path: "".into(),
+                ytdlp_path: "".into(),
+                user_agent_profiles: HashMap::new(),
+                resolver_concurrency: default_resolver_concurrency(),
+                resolver_min_interval: default_resolver_min_interval(),
+                live_info_cache_ttl: default_live_info_cache_ttl(),
+                poll_min_interval: default_poll_min_interval(),
+                poll_max_interval: default_poll_max_interval(),
+                backup_dir: "".into(),
+                backup_count: default_backup_count(),
+                backup_interval: default_backup_interval(),
+                trash_retention_days: default_trash_retention_days(),
+            }
+        }
+    }
+
+    pub fn default_resolver_concurrency() -> usize {
+        2
+    }
+
+    pub fn default_resolver_min_interval() -> u64 {
+        1000
+    }
+
+    pub fn default_live_info_cache_ttl() -> u64 {
+        30
+    }
+
+    pub fn default_poll_min_interval() -> u64 {
+        30
+    }
+
+    pub fn default_poll_max_interval() -> u64 {
+        600
+    }
+
+    pub fn default_backup_count() -> usize {
+        7
+    }
+
+    pub fn default_backup_interval() -> u64 {
+        24
+    }
+
+    pub fn default_trash_retention_days() -> u64 {
+        30
+    }
+
+    impl AppConfig {
+        /// 平台录制时使用的 User-Agent
+        pub fn user_agent(&self, platform_kind: &PlatformKind) -> String {
+            self.user_agent_profiles
+                .get(&platform_kind.to_string())
+                .cloned()
+                .unwrap_or_default()
+                .user_agent()
+        }
+    }
+
+    impl UserAgentProfile {
+        pub fn user_agent(&self) -> String {
+            match self {
+                Self::Chrome => "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36".into(),
+                Self::Firefox => "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:109.0) Gecko/20100101 Firefox/115.0".into(),
+                Self::Safari => "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Safari/605.1.15".into(),
+                Self::Mobile => "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Mobile/15E148 Safari/604.1".into(),
+                Self::Custom(user_agent) => user_agent.clone(),
             }
         }
     }
@@ -238,18 +718,30 @@ pub mod history {
     use super::*;
 
     impl RecordingHistory {
-        pub fn new(url: &str, path: &str) -> Self {
+        pub fn new(url: &str, path: &str, platform_kind: PlatformKind) -> Self {
             Self {
                 url: url.into(),
                 path: path.into(),
                 status: RecordStatus::Recording,
                 start_time: Utc::now().timestamp_millis(),
                 end_time: 0,
+                stream: None,
+                trash: None,
+                fingerprint: "".into(),
+                platform_kind: Some(platform_kind),
                 file_size: 0,
                 deleted: false,
                 live_info: None,
             }
         }
+
+        /// 文件现在所在的路径，在回收站中时是回收站中的路径
+        pub fn file_path(&self) -> &str {
+            match &self.trash {
+                Some(trash) => &trash.path,
+                None => &self.path,
+            }
+        }
     }
 }
 
@@ -273,6 +765,25 @@ pub mod plan {
                 stream_protocol,
                 stream_resolution,
                 option: option.unwrap_or_default(),
+                display_name: None,
+                quality_policy: None,
+                conditions: RecordCondition::default(),
+                tags: vec![],
+            }
+        }
+
+        /// 计划的选流策略，旧计划没有策略时，按照保存的协议和清晰度名称选择
+        pub fn quality_policy(&self) -> QualityPolicy {
+            match &self.quality_policy {
+                Some(policy) => policy.clone(),
+                None => QualityPolicy {
+                    preference: if self.stream_resolution.is_empty() {
+                        QualityPreference::Highest
+                    } else {
+                        QualityPreference::Label(self.stream_resolution.clone())
+                    },
+                    protocols: vec![self.stream_protocol.clone()],
+                },
             }
         }
 
@@ -280,15 +791,161 @@ pub mod plan {
             RecordingPlan::new(url, StreamingProtocol::Flv, "".into(), None)
         }
     }
+
+    impl RecordingPlan {
+        /// 按字段修改计划，并更新 updated_at
+        pub fn apply(&mut self, patch: PlanPatch) {
+            if let Some(strategy) = patch.strategy {
+                self.strategy = strategy;
+            }
+            if let Some(stream_protocol) = patch.stream_protocol {
+                self.stream_protocol = stream_protocol;
+            }
+            if let Some(stream_resolution) = patch.stream_resolution {
+                self.stream_resolution = stream_resolution;
+            }
+            if let Some(enabled) = patch.enabled {
+                self.enabled = enabled;
+            }
+            if let Some(option) = patch.option {
+                self.option = option;
+            }
+            if let Some(display_name) = patch.display_name {
+                let display_name = display_name.trim();
+                self.display_name = (!display_name.is_empty()).then(|| display_name.to_string());
+            }
+            if let Some(quality_policy) = patch.quality_policy {
+                self.quality_policy = quality_policy;
+            }
+            if let Some(conditions) = patch.conditions {
+                self.conditions = conditions;
+            }
+            if let Some(tags) = patch.tags {
+                self.tags.clear();
+                for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+                    if !self.tags.iter().any(|t| t == tag) {
+                        self.tags.push(tag.into());
+                    }
+                }
+            }
+            self.updated_at = Utc::now().timestamp_millis();
+        }
+
+        /// 用分组的默认设置补全计划，按 tags 的顺序，前面的分组优先
+        pub fn inherit(&mut self, groups: &[PlanGroup]) {
+            let groups: Vec<&PlanGroup> = self
+                .tags
+                .iter()
+                .filter_map(|tag| groups.iter().find(|g| g.name == *tag))
+                .collect();
+            if self.quality_policy.is_none() {
+                self.quality_policy = groups.iter().find_map(|g| g.quality_policy.clone());
+            }
+            if self.option == RecordingOption::default() {
+                if let Some(option) = groups.iter().find_map(|g| g.option.clone()) {
+                    self.option = option;
+                }
+            }
+        }
+
+        /// 检查计划的策略、选项和条件是否有效
+        pub fn validate(&self) -> anyhow::Result<()> {
+            use anyhow::bail;
+            match self.strategy {
+                RecordingStrategy::Timed(start, end) if start >= end => {
+                    bail!("end time must be later than start time")
+                }
+                RecordingStrategy::TimedWithDuration(_, duration)
+                | RecordingStrategy::AnchorLiveWithDuration(duration)
+                    if duration <= 0 =>
+                {
+                    bail!("duration must be greater than 0")
+                }
+                _ => {}
+            }
+            if let Some(proxy) = self.option.use_proxy.as_deref().filter(|p| !p.is_empty()) {
+                reqwest::Url::parse(proxy).map_err(|e| anyhow::anyhow!("invalid proxy: {}", e))?;
+            }
+            if self.option.delete_original_file && !self.option.auto_convert_to_mp4 {
+                bail!("delete original file requires auto convert to mp4");
+            }
+            if let Some(policy) = &self.quality_policy {
+                match &policy.preference {
+                    QualityPreference::AtMost(0) => bail!("max height must be greater than 0"),
+                    QualityPreference::Label(label) if label.trim().is_empty() => {
+                        bail!("quality label is empty")
+                    }
+                    _ => {}
+                }
+            }
+            crate::condition::validate(&self.conditions, &PlatformKind::from(&self.url))
+        }
+    }
+
+    impl RecordCondition {
+        /// 是否没有设置任何条件
+        pub fn is_empty(&self) -> bool {
+            let blank = |v: &Option<String>| !v.as_deref().is_some_and(|v| !v.trim().is_empty());
+            blank(&self.title_include)
+                && blank(&self.title_exclude)
+                && blank(&self.category)
+                && self.min_viewer_count.is_none()
+        }
+    }
+}
+
+pub mod live {
+    use super::*;
+
+    impl LiveSample {
+        /// 用当前的直播间信息生成一条记录
+        pub fn new(info: &LiveInfo) -> Self {
+            Self {
+                time: Utc::now().timestamp_millis(),
+                viewer_count: info.viewer_count.clone(),
+                viewers: crate::condition::parse_viewer_count(&info.viewer_count),
+                title: info.title.clone(),
+                room_cover: info.room_cover.clone(),
+            }
+        }
+    }
+
+    impl LiveInfo {
+        /// 一个未开播的空直播信息，由各平台的解析器填充
+        pub fn new(url: &str, platform_kind: PlatformKind) -> Self {
+            Self {
+                url: url.into(),
+                anchor_name: "".into(),
+                anchor_avatar: "".into(),
+                title: "".into(),
+                status: LiveStatus::NotLive,
+                viewer_count: "".into(),
+                room_cover: "".into(),
+                category: "".into(),
+                streams: vec![],
+                platform_kind,
+                updated_at: 0,
+            }
+        }
+    }
 }
 
 pub mod platform {
     use super::*;
 
+    impl PlatformKind {
+        /// 除了自定义平台以外的所有平台，包括 Unknown
+        pub fn builtin() -> impl Iterator<Item = PlatformKind> {
+            PlatformKind::iter().filter(|kind| !matches!(kind, PlatformKind::Custom(_)))
+        }
+    }
+
     // 为 PlatformKind 实现 From 方法，用来从网址字符串转换成 PlatformKind
+    // 自定义平台和外部解析器只使用 platform::reload 加载好的内容，转换时不做 IO
     impl<T: AsRef<str>> From<T> for PlatformKind {
         fn from(url: T) -> Self {
-            let url = url.as_ref().to_lowercase();
+            let raw = url.as_ref();
+            let url = raw.to_lowercase();
             match url {
                 _ if url.starts_with("https://live.douyin.com/")
                     || url.starts_with("https://v.douyin.com/")
@@ -305,12 +962,109 @@ pub mod platform {
                 _ if url.starts_with("https://www.huya.com/") || url == "huya" => {
                     PlatformKind::Huya
                 }
-                _ => PlatformKind::Unknown,
+                _ if url.starts_with("https://live.bilibili.com/") || url == "bilibili" => {
+                    PlatformKind::Bilibili
+                }
+                // 和 platform::resolvers 的顺序一致：内置平台都不匹配时，先看用户自定义的平台，
+                // 自定义平台可以覆盖直接流地址，然后是直接流地址，最后交给外部解析器
+                _ => crate::platform::custom::match_kind(raw)
+                    .or_else(|| {
+                        (crate::platform::direct::is_direct_url(&url) || url == "direct")
+                            .then_some(PlatformKind::Direct)
+                    })
+                    .or_else(|| crate::platform::external::match_kind(raw))
+                    .unwrap_or(PlatformKind::Unknown),
             }
         }
     }
 }
 
+// 自定义平台定义，可以保存在数据库中，也可以作为 json 或 toml 文件放在配置目录的 platforms 文件夹下
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomPlatform {
+    // 平台名，同时作为 PlatformKind::Custom 的值和保存文件夹名
+    pub name: String,
+    // 匹配直播间网址的正则
+    pub url_pattern: String,
+    // 实际请求的地址，可以用 $1 或 ${name} 引用 url_pattern 的捕获组，为空时直接请求直播间网址
+    #[serde(default)]
+    pub request_url: Option<String>,
+    // 请求头
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    // 拉流时需要的请求头，例如 Referer
+    #[serde(default)]
+    pub stream_headers: HashMap<String, String>,
+    // 提取规则
+    pub rules: ExtractRules,
+}
+
+// 从响应内容中提取直播信息的规则，未设置的字段保持为空
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExtractRules {
+    pub anchor_name: Option<ExtractRule>,
+    pub anchor_avatar: Option<ExtractRule>,
+    pub title: Option<ExtractRule>,
+    pub viewer_count: Option<ExtractRule>,
+    pub room_cover: Option<ExtractRule>,
+    pub category: Option<ExtractRule>,
+    // 直播状态，提取到的值等于 live_value 时认为正在直播；没有设置 live_value 时，提取到非空值就认为正在直播
+    pub live_status: Option<ExtractRule>,
+    pub live_value: Option<String>,
+    // 直播流地址，没有设置 live_status 时，提取到流地址就认为正在直播
+    pub streams: Vec<StreamRule>,
+}
+
+// 提取规则，正则取第一个捕获组（没有捕获组时取整个匹配），JsonPath 要求响应是 json
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExtractRule {
+    Regex(String),
+    JsonPath(String),
+}
+
+// 直播流提取规则，一条规则可以提取到多个地址
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamRule {
+    // 清晰度名称
+    #[serde(default)]
+    pub resolution: String,
+    pub rule: ExtractRule,
+}
+
+// 平台账号凭据的元信息，cookie 本身保存在系统钥匙串里，不写入数据库
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlatformCredential {
+    pub platform_kind: PlatformKind,
+    // 外部解析器的 cookie 按域名分别保存，只发给匹配的网址，其它平台为空
+    #[serde(default)]
+    pub host: String,
+    // 更新时间，时间戳毫秒数
+    pub updated_at: i64,
+    // cookie 中最早的过期时间，时间戳毫秒数，如果是 0, 说明不知道过期时间
+    pub expires_at: i64,
+    // 上次校验的结果
+    pub status: CredentialStatus,
+    // 上次校验时间，时间戳毫秒数，如果是 0, 说明没有校验过
+    pub checked_at: i64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub enum CredentialStatus {
+    // 未校验
+    Unchecked,
+    // 有效
+    Valid,
+    // 平台返回未登录，需要重新登录
+    Invalid,
+    // 已过期，需要重新登录
+    Expired,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ProxyConfig {
@@ -326,3 +1080,27 @@ pub struct QueryHistory {
     pub platform_kind: String,
     pub created_at: i64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_patch_quality_policy() {
+        let patch = |json: &str| serde_json::from_str::<PlanPatch>(json).unwrap();
+        assert!(patch("{}").quality_policy.is_none());
+        assert!(matches!(
+            patch(r#"{"qualityPolicy":null}"#).quality_policy,
+            Some(None)
+        ));
+
+        let mut plan = RecordingPlan::new_with_url("https://live.douyin.com/1");
+        plan.apply(patch(r#"{"qualityPolicy":{"preference":"Lowest"}}"#));
+        assert!(plan.quality_policy.is_some());
+        // 没有提交的字段保持不变，null 清除
+        plan.apply(patch("{}"));
+        assert!(plan.quality_policy.is_some());
+        plan.apply(patch(r#"{"qualityPolicy":null}"#));
+        assert!(plan.quality_policy.is_none());
+    }
+}
@@ -0,0 +1,1191 @@
+use crate::{
+    credential, kv,
+    model::{
+        JsonValue, LiveInfo, LiveStatus, PlatformKind, Stream, StreamQuality, StreamingProtocol,
+    },
+    request, throttle,
+};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use regex::Regex;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT_LANGUAGE, COOKIE, REFERER, USER_AGENT};
+use reqwest::Url;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 直播间解析器，内置平台和自定义平台都实现这个 trait，统一注册到解析器列表中
+#[async_trait]
+pub trait Resolver: Send + Sync {
+    /// 解析器对应的平台
+    fn platform_kind(&self) -> PlatformKind;
+
+    /// 是否能处理这个网址
+    fn matches(&self, url: &str) -> bool;
+
+    /// 获取直播间信息
+    async fn get_live_info(&self, url: &str) -> Result<LiveInfo>;
+}
+
+/// 所有的解析器，内置平台在前，然后是自定义平台和直接流地址，外部解析器在最后
+pub fn resolvers() -> Vec<Arc<dyn Resolver>> {
+    let mut resolvers: Vec<Arc<dyn Resolver>> = vec![Arc::new(douyin::DouyinResolver)];
+    resolvers.extend(custom::resolvers());
+    // 直接流地址放在后面，自定义平台可以覆盖它
+    resolvers.push(Arc::new(direct::DirectResolver));
+    // 外部解析器可以处理任意网址，作为最后的兜底
+    if let Some(external) = external::ExternalResolver::from_config() {
+        resolvers.push(Arc::new(external));
+    }
+    resolvers
+}
+
+/// 重新加载自定义平台和外部解析器的配置，启动时和它们变化时调用
+/// PlatformKind::from 只读取这里加载好的内容，不会读取数据库或文件
+pub fn reload() {
+    custom::reload();
+    external::reload();
+}
+
+/// 找到第一个能处理这个网址的解析器
+pub fn find_resolver(url: &str) -> Option<Arc<dyn Resolver>> {
+    resolvers().into_iter().find(|r| r.matches(url))
+}
+
+/// 通过解析器获取直播间信息，缓存未过期时直接返回缓存
+pub async fn get_live_info(url: &str) -> Result<LiveInfo> {
+    let url = canonicalize_url(url).await?;
+    if let Some(info) = cached_live_info(&url)? {
+        return Ok(info);
+    }
+    let resolver = find_resolver(&url).ok_or_else(|| anyhow!("Unknown platform: {}", url))?;
+    resolve(resolver.as_ref(), &url).await
+}
+
+/// 添加计划前检查网址能否解析，外部解析器不支持的网址直接报错，而不是一直显示未开播
+pub async fn check_supported(url: &str) -> Result<()> {
+    let Some(resolver) = find_resolver(url) else {
+        return Ok(());
+    };
+    if resolver.platform_kind() != PlatformKind::External {
+        return Ok(());
+    }
+    let Some(external) = external::ExternalResolver::from_config() else {
+        return Ok(());
+    };
+    let url = url.to_string();
+    tokio::task::spawn_blocking(move || external.check_supported(&url)).await??;
+    Ok(())
+}
+
+/// 在平台的限流规则下请求解析器，并把结果写入缓存
+pub async fn resolve(resolver: &dyn Resolver, url: &str) -> Result<LiveInfo> {
+    let mut info = throttle::run(&resolver.platform_kind(), || resolver.get_live_info(url)).await?;
+    info.updated_at = Utc::now().timestamp_millis();
+    kv::live::add(&info)?;
+    Ok(info)
+}
+
+/// 替前端的解析器请求平台接口，返回响应内容
+/// 保存了账号 cookie 并且请求的是平台自己的域名时带上它，替换掉解析器自带的匿名 cookie
+/// 和后端的解析器一样受平台的限流规则约束，前端每 10 秒的轮询也不会请求得过于频繁
+pub async fn request_text(
+    platform_kind: &PlatformKind,
+    url: &str,
+    headers: &HashMap<String, String>,
+    body: Option<String>,
+) -> Result<String> {
+    let mut headers = request::header_map(headers)?;
+    if let Some(cookie) = credential::cookie_for(platform_kind, url) {
+        headers.insert(COOKIE, HeaderValue::from_str(&cookie)?);
+    }
+    throttle::run(platform_kind, || async move {
+        // 状态码要变成错误，限流器才能识别 429 和 403 并退避
+        let text = request::send(url, headers, body, 10)
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(text)
+    })
+    .await
+}
+
+/// 缓存中还在有效期内的直播信息，不会请求网络
+pub fn cached_live_info(url: &str) -> Result<Option<LiveInfo>> {
+    let ttl = kv::config::get()?.live_info_cache_ttl as i64 * 1000;
+    let now = Utc::now().timestamp_millis();
+    Ok(kv::live::get(url)?.filter(|info| info.updated_at > 0 && now - info.updated_at < ttl))
+}
+
+/// 根据流地址猜测协议，m3u8 认为是 hls，其它都当作 flv
+pub fn guess_protocol(stream_url: &str) -> StreamingProtocol {
+    let path = stream_url.split('?').next().unwrap_or_default();
+    if path.to_lowercase().ends_with(".m3u8") {
+        StreamingProtocol::Hls
+    } else {
+        StreamingProtocol::Flv
+    }
+}
+
+/// 展开短链接时，最多跟随的跳转次数
+const MAX_REDIRECT_HOPS: usize = 8;
+
+/// 分享短链接的前缀，这些链接每次分享都会变化，不能直接作为计划的键
+const SHORT_LINK_PREFIXES: &[&str] = &["https://v.douyin.com/", "http://v.douyin.com/"];
+
+/// 是否是分享短链接
+pub fn is_short_link(url: &str) -> bool {
+    let url = url.trim().to_lowercase();
+    SHORT_LINK_PREFIXES
+        .iter()
+        .any(|prefix| url.starts_with(prefix))
+}
+
+/// 将用户输入的网址转换为规范的直播间网址
+/// 短链接会沿着跳转链展开，其它网址去掉首尾空白后原样返回
+pub async fn canonicalize_url(url: &str) -> Result<String> {
+    let url = url.trim();
+    if !is_short_link(url) {
+        return Ok(url.to_string());
+    }
+    let chain = request::follow_redirects(url, short_link_headers(), MAX_REDIRECT_HOPS).await?;
+    // 从最后一跳往前找，优先使用最终地址
+    for hop in chain.iter().rev() {
+        let Ok(hop) = Url::parse(hop) else {
+            continue;
+        };
+        if let Some(room_url) = douyin::live_room_url(&hop) {
+            return Ok(room_url);
+        }
+        if let Some(room_id) = douyin::reflow_room_id(&hop) {
+            return douyin::live_room_url_by_room_id(&room_id, &hop).await;
+        }
+    }
+    Err(anyhow!(
+        "can not find live room url in redirect chain: {:?}",
+        chain
+    ))
+}
+
+fn short_link_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    // 使用手机端 UA，分享链接会跳转到 reflow 页面，里面带有 room_id
+    headers.insert(
+        USER_AGENT,
+        HeaderValue::from_static("Mozilla/5.0 (Linux; Android 11; SAMSUNG SM-G973U) AppleWebKit/537.36 (KHTML, like Gecko) SamsungBrowser/14.2 Chrome/87.0.4280.141 Mobile Safari/537.36"),
+    );
+    headers
+}
+
+mod douyin {
+    use super::*;
+
+    pub(super) struct DouyinResolver;
+
+    #[async_trait]
+    impl Resolver for DouyinResolver {
+        fn platform_kind(&self) -> PlatformKind {
+            PlatformKind::Douyin
+        }
+
+        fn matches(&self, url: &str) -> bool {
+            Url::parse(url)
+                .ok()
+                .and_then(|url| live_room_url(&url))
+                .is_some()
+        }
+
+        async fn get_live_info(&self, url: &str) -> Result<LiveInfo> {
+            let mut info = LiveInfo::new(url, PlatformKind::Douyin);
+            let html = request::get(url, headers()?)
+                .await?
+                .error_for_status()?
+                .text()
+                .await?;
+            parse_html_and_fill_live_info(&html, &mut info)?;
+            Ok(info)
+        }
+    }
+
+    // 解析页面里的 roomStore，填充 LiveInfo
+    fn parse_html_and_fill_live_info(html: &str, info: &mut LiveInfo) -> Result<()> {
+        let state = Regex::new(r#"(\{\\"state\\":.*?)\]\\n"\]"#)?
+            .captures(html)
+            .and_then(|c| c.get(1))
+            .ok_or_else(|| anyhow!("can not match json string"))?
+            .as_str()
+            .replace('\\', "")
+            .replace("u0026", "&");
+        let Some(room_store) = Regex::new(r#""roomStore":(.*?),"linkmicStore""#)?
+            .captures(&state)
+            .and_then(|c| c.get(1))
+            .map(|m| m.as_str().to_string())
+        else {
+            return Ok(());
+        };
+        if let Some(name) = Regex::new(r#""nickname":"(.*?)","avatar_thumb"#)?
+            .captures(&room_store)
+            .and_then(|c| c.get(1))
+        {
+            info.anchor_name = name.as_str().into();
+        }
+        let room_store = format!(
+            "{}}}}}}}",
+            room_store
+                .split(r#","has_commerce_goods""#)
+                .next()
+                .unwrap_or_default()
+        );
+        let json: JsonValue = serde_json::from_str(&room_store)?;
+        let room = &json["roomInfo"]["room"];
+        // 2: 直播中，4: 未直播
+        if room["status"].as_i64().unwrap_or(4) != 2 {
+            return Ok(());
+        }
+        info.title = room["title"].as_str().unwrap_or_default().into();
+        info.anchor_avatar = room["owner"]["avatar_thumb"]["url_list"][0]
+            .as_str()
+            .unwrap_or_default()
+            .into();
+        info.viewer_count = room["user_count_str"].as_str().unwrap_or_default().into();
+        info.category = room["partition_road_map"]["partition"]["title"]
+            .as_str()
+            .unwrap_or_default()
+            .into();
+        let stream_url = &room["stream_url"];
+        let maps = [
+            (&stream_url["flv_pull_url"], StreamingProtocol::Flv),
+            (&stream_url["hls_pull_url_map"], StreamingProtocol::Hls),
+        ];
+        for (map, protocol) in maps {
+            let Some(map) = map.as_object() else {
+                continue;
+            };
+            for (resolution, url) in map {
+                info.streams.push(Stream {
+                    url: url.as_str().unwrap_or_default().into(),
+                    resolution: resolution.clone(),
+                    protocol: protocol.clone(),
+                    quality: None,
+                    // CDN 会检查 Referer
+                    headers: HashMap::from([(
+                        "Referer".to_string(),
+                        "https://live.douyin.com/".to_string(),
+                    )]),
+                });
+            }
+        }
+        info.status = LiveStatus::Live;
+        Ok(())
+    }
+
+    fn headers() -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            USER_AGENT,
+            HeaderValue::from_static(
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:109.0) Gecko/20100101 Firefox/115.0",
+            ),
+        );
+        headers.insert(
+            ACCEPT_LANGUAGE,
+            HeaderValue::from_static("zh-CN,zh;q=0.8,zh-TW;q=0.7,zh-HK;q=0.5,en-US;q=0.3,en;q=0.2"),
+        );
+        headers.insert(
+            REFERER,
+            HeaderValue::from_static("https://live.douyin.com/"),
+        );
+        // 页面需要 ttwid 才会返回直播间数据，登录过的话使用账号的 cookie
+        let cookie = match credential::get_cookie(&PlatformKind::Douyin) {
+            Some(cookie) => HeaderValue::from_str(&cookie)?,
+            None => HeaderValue::from_static("ttwid=1%7CB1qls3GdnZhUov9o2NxOMxxYS2ff6OSvEWbv0ytbES4%7C1680522049%7C280d802d6d478e3e78d0c807f7c487e7ffec0ae4e5fdd6a0fe74c3c6af149511"),
+        };
+        headers.insert(COOKIE, cookie);
+        Ok(headers)
+    }
+
+    /// 形如 https://live.douyin.com/790601393533?xxx 的地址，去掉参数后就是规范地址
+    pub(super) fn live_room_url(url: &Url) -> Option<String> {
+        if url.host_str()? != "live.douyin.com" {
+            return None;
+        }
+        let web_rid = url.path_segments()?.next()?;
+        if web_rid.is_empty() || !web_rid.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        Some(format!("https://live.douyin.com/{}", web_rid))
+    }
+
+    /// 形如 https://webcast.amemv.com/douyin/webcast/reflow/7384xxx?sec_user_id=xxx 的地址，取出 room_id
+    pub(super) fn reflow_room_id(url: &Url) -> Option<String> {
+        let mut segments = url.path_segments()?;
+        segments.find(|s| *s == "reflow")?;
+        let room_id = segments.next()?;
+        if room_id.is_empty() || !room_id.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        Some(room_id.to_string())
+    }
+
+    /// room_id 不是直播间网址里的 web_rid，需要通过 reflow 接口查询
+    pub(super) async fn live_room_url_by_room_id(
+        room_id: &str,
+        reflow_url: &Url,
+    ) -> Result<String> {
+        let sec_user_id = reflow_url
+            .query_pairs()
+            .find(|(k, _)| k == "sec_user_id")
+            .map(|(_, v)| v.to_string())
+            .unwrap_or_default();
+        let api = format!(
+            "https://webcast.amemv.com/webcast/room/reflow/info/?type_id=0&live_id=1&room_id={}&sec_user_id={}&app_id=1128",
+            room_id, sec_user_id
+        );
+        let mut headers = short_link_headers();
+        headers.insert(
+            REFERER,
+            HeaderValue::from_static("https://live.douyin.com/"),
+        );
+        let json: JsonValue = request::get(&api, headers).await?.json().await?;
+        let web_rid = json["data"]["room"]["owner"]["web_rid"]
+            .as_str()
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| anyhow!("can not find web_rid for room_id: {}", room_id))?;
+        Ok(format!("https://live.douyin.com/{}", web_rid))
+    }
+}
+
+pub mod direct {
+    use super::*;
+    use crate::{ffmpeg, kv};
+
+    /// 直接流地址支持的协议
+    const STREAM_SCHEMES: &[&str] = &["rtmp", "rtmps", "rtsp", "srt"];
+    /// 直接流地址支持的扩展名
+    const STREAM_EXTENSIONS: &[&str] = &[".flv", ".m3u8"];
+
+    /// 是否是直接的流地址，而不是直播间网址
+    pub fn is_direct_url(url: &str) -> bool {
+        let Ok(url) = Url::parse(url.trim()) else {
+            return false;
+        };
+        if STREAM_SCHEMES.contains(&url.scheme()) {
+            return true;
+        }
+        let path = url.path().to_lowercase();
+        matches!(url.scheme(), "http" | "https")
+            && STREAM_EXTENSIONS.iter().any(|ext| path.ends_with(ext))
+    }
+
+    pub(super) struct DirectResolver;
+
+    #[async_trait]
+    impl Resolver for DirectResolver {
+        fn platform_kind(&self) -> PlatformKind {
+            PlatformKind::Direct
+        }
+
+        fn matches(&self, url: &str) -> bool {
+            is_direct_url(url)
+        }
+
+        async fn get_live_info(&self, url: &str) -> Result<LiveInfo> {
+            let mut info = LiveInfo::new(url, PlatformKind::Direct);
+            // 没有主播名，优先使用计划里用户指定的名称
+            let display_name = kv::plan::get(url.to_string())?.and_then(|p| p.display_name);
+            info.anchor_name = display_name
+                .filter(|name| !name.trim().is_empty())
+                .unwrap_or_else(|| default_name(url));
+            info.title = info.anchor_name.clone();
+            if probe(url).await? {
+                info.status = LiveStatus::Live;
+                info.streams.push(Stream {
+                    url: url.into(),
+                    resolution: "default".into(),
+                    protocol: guess_protocol(url),
+                    quality: None,
+                    headers: HashMap::new(),
+                });
+            }
+            Ok(info)
+        }
+    }
+
+    /// 没有指定名称时，使用域名加文件名，例如 cdn.example.com_room1
+    fn default_name(url: &str) -> String {
+        let Ok(url) = Url::parse(url) else {
+            return "direct".into();
+        };
+        let host = url.host_str().unwrap_or("direct");
+        let stem = url
+            .path_segments()
+            .and_then(|mut segments| segments.rfind(|s| !s.is_empty()))
+            .map(|s| s.split('.').next().unwrap_or_default())
+            .unwrap_or_default();
+        if stem.is_empty() {
+            host.into()
+        } else {
+            format!("{}_{}", host, stem)
+        }
+    }
+
+    /// http 地址请求一下看状态码，其它协议交给 ffmpeg 读取一下
+    async fn probe(url: &str) -> Result<bool> {
+        if url.starts_with("http://") || url.starts_with("https://") {
+            let status = match request::try_get(url, HeaderMap::new(), 5).await {
+                Ok(resp) => resp.status(),
+                Err(_) => return Ok(false),
+            };
+            return Ok(status.is_success());
+        }
+        let ffmpeg_path = kv::config::get()?.ffmpeg_path;
+        let url = url.to_string();
+        let live = tokio::task::spawn_blocking(move || ffmpeg::probe(&ffmpeg_path, &url)).await??;
+        Ok(live)
+    }
+}
+
+pub mod external {
+    use super::*;
+    use crate::kv;
+    use once_cell::sync::Lazy;
+    use std::{fmt, process::Command, sync::RwLock};
+
+    /// 外部解析器的错误，调用方可以据此区分未开播和真正的失败
+    #[derive(Debug)]
+    pub enum ExternalResolverError {
+        /// 没有配置 streamlink 或 yt-dlp 的路径
+        NotConfigured,
+        /// 无法启动可执行文件，通常是路径不对
+        Spawn(std::io::Error),
+        /// 工具明确表示没有可用的流，一般是未开播
+        NoStreams(String),
+        /// 工具不支持这个网址，和未开播不同，不会随时间变化
+        Unsupported(String),
+        /// 工具执行失败
+        Failed(String),
+        /// 输出不是预期的 json
+        InvalidOutput(serde_json::Error),
+    }
+
+    impl fmt::Display for ExternalResolverError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::NotConfigured => write!(f, "streamlink or yt-dlp is not configured"),
+                Self::Spawn(e) => write!(f, "can not run external resolver: {}", e),
+                Self::NoStreams(message) => write!(f, "no playable streams: {}", message),
+                Self::Unsupported(url) => write!(f, "url is not supported: {}", url),
+                Self::Failed(message) => write!(f, "external resolver failed: {}", message),
+                Self::InvalidOutput(e) => write!(f, "invalid external resolver output: {}", e),
+            }
+        }
+    }
+
+    impl std::error::Error for ExternalResolverError {}
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Tool {
+        Streamlink,
+        Ytdlp,
+    }
+
+    #[derive(Clone)]
+    pub struct ExternalResolver {
+        tool: Tool,
+        path: String,
+    }
+
+    /// 按配置创建好的外部解析器，没有配置时为 None
+    static CONFIGURED: Lazy<RwLock<Option<ExternalResolver>>> = Lazy::new(|| RwLock::new(None));
+
+    /// 重新读取配置中的 streamlink 和 yt-dlp 路径
+    pub fn reload() {
+        *CONFIGURED.write().unwrap() = ExternalResolver::load();
+    }
+
+    impl ExternalResolver {
+        /// 配置的外部解析器，都没有配置时返回 None
+        pub fn from_config() -> Option<Self> {
+            CONFIGURED.read().unwrap().clone()
+        }
+
+        /// 根据配置创建，优先使用 streamlink
+        fn load() -> Option<Self> {
+            let config = kv::config::get()
+                .map_err(|e| eprintln!("Could not get config: {}", e))
+                .ok()?;
+            if !config.streamlink_path.trim().is_empty() {
+                Some(Self {
+                    tool: Tool::Streamlink,
+                    path: config.streamlink_path,
+                })
+            } else if !config.ytdlp_path.trim().is_empty() {
+                Some(Self {
+                    tool: Tool::Ytdlp,
+                    path: config.ytdlp_path,
+                })
+            } else {
+                None
+            }
+        }
+
+        /// 检查工具是否支持这个网址，streamlink 用 --can-handle-url 检查
+        /// yt-dlp 有通用的解析，不能提前判断，总是认为支持
+        pub fn check_supported(&self, url: &str) -> Result<(), ExternalResolverError> {
+            if self.tool == Tool::Ytdlp {
+                return Ok(());
+            }
+            let mut cmd = Command::new(&self.path);
+            #[cfg(target_os = "windows")]
+            {
+                use std::os::windows::process::CommandExt;
+                cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+            }
+            let status = cmd
+                .args(["--can-handle-url", url])
+                .status()
+                .map_err(ExternalResolverError::Spawn)?;
+            if status.success() {
+                Ok(())
+            } else {
+                Err(ExternalResolverError::Unsupported(url.into()))
+            }
+        }
+
+        /// 执行外部工具，返回解析好的直播信息
+        pub fn resolve(&self, url: &str) -> Result<LiveInfo, ExternalResolverError> {
+            let mut args: Vec<String> = match self.tool {
+                Tool::Streamlink => vec!["--json".into(), "--http-timeout".into(), "10".into()],
+                Tool::Ytdlp => vec![
+                    "--dump-single-json".into(),
+                    "--no-warnings".into(),
+                    "--no-playlist".into(),
+                    "--socket-timeout".into(),
+                    "10".into(),
+                ],
+            };
+            // 只带上域名和网址匹配的 cookie，避免把一个网站的登录信息发给其它网站
+            if let Some(cookie) = credential::cookie_for(&PlatformKind::External, url) {
+                let (flag, header) = match self.tool {
+                    Tool::Streamlink => ("--http-header", format!("Cookie={}", cookie)),
+                    Tool::Ytdlp => ("--add-header", format!("Cookie:{}", cookie)),
+                };
+                args.push(flag.into());
+                args.push(header);
+            }
+            args.push(url.into());
+            let mut cmd = Command::new(&self.path);
+            // 特定于 windows 的实现，使用 CommandExt，避免出现黑窗口
+            #[cfg(target_os = "windows")]
+            {
+                use std::os::windows::process::CommandExt;
+                cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+            }
+            let output = cmd
+                .args(&args)
+                .output()
+                .map_err(ExternalResolverError::Spawn)?;
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            match self.tool {
+                Tool::Streamlink => parse_streamlink(url, &stdout),
+                Tool::Ytdlp if output.status.success() => parse_ytdlp(url, &stdout),
+                Tool::Ytdlp => {
+                    let message = stderr.trim().to_string();
+                    // 未开播或者直播还没开始时，yt-dlp 会以错误退出
+                    if message.contains("is offline")
+                        || message.contains("not currently live")
+                        || message.contains("will begin")
+                    {
+                        Err(ExternalResolverError::NoStreams(message))
+                    } else {
+                        Err(ExternalResolverError::Failed(message))
+                    }
+                }
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Resolver for ExternalResolver {
+        fn platform_kind(&self) -> PlatformKind {
+            PlatformKind::External
+        }
+
+        fn matches(&self, url: &str) -> bool {
+            url.starts_with("http://") || url.starts_with("https://")
+        }
+
+        async fn get_live_info(&self, url: &str) -> Result<LiveInfo> {
+            let resolver = self.clone();
+            let owned_url = url.to_string();
+            let result = tokio::task::spawn_blocking(move || resolver.resolve(&owned_url)).await?;
+            match result {
+                Ok(info) => Ok(info),
+                // 没有可用的流就是未开播，不当作错误
+                Err(ExternalResolverError::NoStreams(_)) => {
+                    Ok(LiveInfo::new(url, PlatformKind::External))
+                }
+                Err(e) => Err(e.into()),
+            }
+        }
+    }
+
+    /// 配置了外部解析器时，所有 http 网址都认为可以处理
+    pub fn match_kind(url: &str) -> Option<PlatformKind> {
+        let resolver = ExternalResolver::from_config()?;
+        resolver.matches(url).then_some(PlatformKind::External)
+    }
+
+    /// 解析 streamlink --json 的输出，失败时输出里是 {"error": "..."}
+    fn parse_streamlink(url: &str, stdout: &str) -> Result<LiveInfo, ExternalResolverError> {
+        let json: JsonValue =
+            serde_json::from_str(stdout).map_err(ExternalResolverError::InvalidOutput)?;
+        if let Some(error) = json["error"].as_str() {
+            return if error.contains("No plugin") {
+                Err(ExternalResolverError::Unsupported(url.into()))
+            } else if error.contains("No playable streams") {
+                Err(ExternalResolverError::NoStreams(error.into()))
+            } else {
+                Err(ExternalResolverError::Failed(error.into()))
+            };
+        }
+        let mut info = LiveInfo::new(url, PlatformKind::External);
+        let metadata = &json["metadata"];
+        info.anchor_name = metadata["author"].as_str().unwrap_or_default().into();
+        info.title = metadata["title"].as_str().unwrap_or_default().into();
+        info.category = metadata["category"].as_str().unwrap_or_default().into();
+        let streams: HashMap<String, JsonValue> =
+            serde_json::from_value(json["streams"].clone()).unwrap_or_default();
+        for (name, stream) in streams {
+            // best 和 worst 是其它清晰度的别名
+            if name == "best" || name == "worst" {
+                continue;
+            }
+            let protocol = match stream["type"].as_str() {
+                Some("hls") => StreamingProtocol::Hls,
+                Some("http") => StreamingProtocol::Flv,
+                _ => continue,
+            };
+            let Some(stream_url) = stream["url"].as_str() else {
+                continue;
+            };
+            info.streams.push(Stream {
+                url: stream_url.into(),
+                resolution: name,
+                protocol,
+                quality: None,
+                headers: string_map(&stream["headers"]),
+            });
+        }
+        if info.streams.is_empty() {
+            return Err(ExternalResolverError::NoStreams(url.into()));
+        }
+        info.status = LiveStatus::Live;
+        Ok(info)
+    }
+
+    /// 解析 yt-dlp --dump-single-json 的输出
+    fn parse_ytdlp(url: &str, stdout: &str) -> Result<LiveInfo, ExternalResolverError> {
+        let json: JsonValue =
+            serde_json::from_str(stdout).map_err(ExternalResolverError::InvalidOutput)?;
+        let mut info = LiveInfo::new(url, PlatformKind::External);
+        info.anchor_name = json["channel"]
+            .as_str()
+            .or_else(|| json["uploader"].as_str())
+            .unwrap_or_default()
+            .into();
+        info.title = json["title"].as_str().unwrap_or_default().into();
+        info.room_cover = json["thumbnail"].as_str().unwrap_or_default().into();
+        info.category = json["categories"][0].as_str().unwrap_or_default().into();
+        info.viewer_count = json["concurrent_view_count"]
+            .as_u64()
+            .map(|count| count.to_string())
+            .unwrap_or_default();
+        // 只录制直播，录像和预告都当作未开播
+        if !json["is_live"].as_bool().unwrap_or(false) {
+            return Ok(info);
+        }
+        for format in json["formats"].as_array().into_iter().flatten() {
+            let Some(stream_url) = format["url"].as_str() else {
+                continue;
+            };
+            let protocol = format["protocol"].as_str().unwrap_or_default();
+            let protocol = if protocol.starts_with("m3u8") {
+                StreamingProtocol::Hls
+            } else if protocol.starts_with("http") && format["ext"].as_str() == Some("flv") {
+                StreamingProtocol::Flv
+            } else {
+                continue;
+            };
+            let resolution = format["format_note"]
+                .as_str()
+                .or_else(|| format["format_id"].as_str())
+                .unwrap_or("default")
+                .to_string();
+            // yt-dlp 直接给出了高度和码率
+            let quality = StreamQuality {
+                height: format["height"].as_u64().map(|h| h as u32),
+                bitrate: format["tbr"].as_f64().map(|b| b as u32),
+                origin: false,
+            };
+            info.streams.push(Stream {
+                url: stream_url.into(),
+                resolution,
+                protocol,
+                quality: Some(quality),
+                headers: string_map(&format["http_headers"]),
+            });
+        }
+        if info.streams.is_empty() {
+            return Err(ExternalResolverError::NoStreams(url.into()));
+        }
+        info.status = LiveStatus::Live;
+        Ok(info)
+    }
+
+    /// 把 json 对象转换为字符串键值对，忽略不是字符串的值
+    fn string_map(value: &JsonValue) -> HashMap<String, String> {
+        value
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+            .collect()
+    }
+
+    /// 检查外部解析器是否可用，返回版本号
+    pub fn version(path: &str) -> Result<String, ExternalResolverError> {
+        let output = Command::new(path)
+            .arg("--version")
+            .output()
+            .map_err(ExternalResolverError::Spawn)?;
+        if !output.status.success() {
+            return Err(ExternalResolverError::Failed(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_parse_streamlink() {
+            let stdout = r#"{"plugin": "twitch", "metadata": {"author": "kriss_drummer", "title": "drums"}, "streams": {"720p": {"type": "hls", "url": "https://example.com/720p.m3u8"}, "best": {"type": "hls", "url": "https://example.com/720p.m3u8"}}}"#;
+            let info = parse_streamlink("https://www.twitch.tv/kriss_drummer", stdout).unwrap();
+            assert_eq!(info.status, LiveStatus::Live);
+            assert_eq!(info.anchor_name, "kriss_drummer");
+            assert_eq!(info.streams.len(), 1);
+            assert_eq!(info.streams[0].protocol, StreamingProtocol::Hls);
+
+            let stdout =
+                r#"{"error": "No playable streams found on this URL: https://www.twitch.tv/x"}"#;
+            assert!(matches!(
+                parse_streamlink("https://www.twitch.tv/x", stdout),
+                Err(ExternalResolverError::NoStreams(_))
+            ));
+
+            let stdout = r#"{"error": "No plugin can handle URL: https://example.com/"}"#;
+            assert!(matches!(
+                parse_streamlink("https://example.com/", stdout),
+                Err(ExternalResolverError::Unsupported(_))
+            ));
+        }
+    }
+}
+
+pub mod custom {
+    use super::*;
+    use crate::{
+        config::config_dir,
+        kv,
+        model::{CustomPlatform, ExtractRule},
+    };
+    use once_cell::sync::{Lazy, OnceCell};
+    use serde_json_path::JsonPath;
+    use std::{
+        path::{Path, PathBuf},
+        sync::RwLock,
+    };
+
+    /// 已加载的自定义平台，None 表示需要重新加载
+    static RESOLVERS: Lazy<RwLock<Option<Vec<Arc<CustomResolver>>>>> =
+        Lazy::new(|| RwLock::new(None));
+
+    /// 自定义平台的解析器，url_pattern 提前编译好
+    pub struct CustomResolver {
+        platform: CustomPlatform,
+        url_regex: Regex,
+    }
+
+    impl CustomResolver {
+        fn new(platform: CustomPlatform) -> Result<Self> {
+            validate(&platform)?;
+            let url_regex = Regex::new(&platform.url_pattern)?;
+            Ok(Self {
+                platform,
+                url_regex,
+            })
+        }
+    }
+
+    #[async_trait]
+    impl Resolver for CustomResolver {
+        fn platform_kind(&self) -> PlatformKind {
+            PlatformKind::Custom(self.platform.name.clone())
+        }
+
+        fn matches(&self, url: &str) -> bool {
+            self.url_regex.is_match(url)
+        }
+
+        async fn get_live_info(&self, url: &str) -> Result<LiveInfo> {
+            let request_url = match &self.platform.request_url {
+                Some(template) if !template.is_empty() => {
+                    let captures = self
+                        .url_regex
+                        .captures(url)
+                        .ok_or_else(|| anyhow!("url does not match: {}", url))?;
+                    let mut request_url = String::new();
+                    captures.expand(template, &mut request_url);
+                    request_url
+                }
+                _ => url.to_string(),
+            };
+            let mut headers = request::header_map(&self.platform.headers)?;
+            if let Some(cookie) = credential::get_cookie(&self.platform_kind()) {
+                headers.insert(COOKIE, HeaderValue::from_str(&cookie)?);
+            }
+            let body = request::get(&request_url, headers)
+                .await?
+                .error_for_status()?
+                .text()
+                .await?;
+            let extractor = Extractor::new(&body);
+            let rules = &self.platform.rules;
+
+            let mut info = LiveInfo::new(url, self.platform_kind());
+            info.anchor_name = extractor.first(&rules.anchor_name)?;
+            info.anchor_avatar = extractor.first(&rules.anchor_avatar)?;
+            info.title = extractor.first(&rules.title)?;
+            info.viewer_count = extractor.first(&rules.viewer_count)?;
+            info.room_cover = extractor.first(&rules.room_cover)?;
+            info.category = extractor.first(&rules.category)?;
+            for stream_rule in &rules.streams {
+                for stream_url in extractor.all(&stream_rule.rule)? {
+                    let resolution = if stream_rule.resolution.is_empty() {
+                        "default".to_string()
+                    } else {
+                        stream_rule.resolution.clone()
+                    };
+                    info.streams.push(Stream {
+                        protocol: guess_protocol(&stream_url),
+                        url: stream_url,
+                        resolution,
+                        quality: None,
+                        headers: self.platform.stream_headers.clone(),
+                    });
+                }
+            }
+            let live = match &rules.live_status {
+                Some(_) => {
+                    let status = extractor.first(&rules.live_status)?;
+                    match &rules.live_value {
+                        Some(live_value) => status == *live_value,
+                        None => !status.is_empty(),
+                    }
+                }
+                None => !info.streams.is_empty(),
+            };
+            if live {
+                info.status = LiveStatus::Live;
+            }
+            Ok(info)
+        }
+    }
+
+    /// 对响应内容执行提取规则，响应只在需要时解析一次 json
+    struct Extractor<'a> {
+        body: &'a str,
+        json: OnceCell<Option<JsonValue>>,
+    }
+
+    impl<'a> Extractor<'a> {
+        fn new(body: &'a str) -> Self {
+            Self {
+                body,
+                json: OnceCell::new(),
+            }
+        }
+
+        /// 提取第一个值，没有设置规则或者没有提取到时返回空字符串
+        fn first(&self, rule: &Option<ExtractRule>) -> Result<String> {
+            let Some(rule) = rule else {
+                return Ok("".into());
+            };
+            Ok(self.all(rule)?.into_iter().next().unwrap_or_default())
+        }
+
+        /// 提取所有值
+        fn all(&self, rule: &ExtractRule) -> Result<Vec<String>> {
+            match rule {
+                ExtractRule::Regex(pattern) => {
+                    let regex = Regex::new(pattern)?;
+                    Ok(regex
+                        .captures_iter(self.body)
+                        .filter_map(|c| c.get(1).or_else(|| c.get(0)))
+                        .map(|m| m.as_str().to_string())
+                        .collect())
+                }
+                ExtractRule::JsonPath(path) => {
+                    let path = JsonPath::parse(path)?;
+                    let json = self
+                        .json
+                        .get_or_init(|| serde_json::from_str(self.body).ok())
+                        .as_ref()
+                        .ok_or_else(|| anyhow!("response is not json"))?;
+                    Ok(path
+                        .query(json)
+                        .all()
+                        .into_iter()
+                        .filter_map(|v| match v {
+                            JsonValue::Null => None,
+                            JsonValue::String(s) => Some(s.clone()),
+                            v => Some(v.to_string()),
+                        })
+                        .collect())
+                }
+            }
+        }
+    }
+
+    /// 检查定义是否可用：平台名不能为空，也不能包含路径分隔符，正则和 JsonPath 都要能编译
+    /// 平台名也不能和内置平台同名，否则会共用凭据、User-Agent、限流和录制历史的索引
+    pub fn validate(platform: &CustomPlatform) -> Result<()> {
+        let name = platform.name.trim();
+        if name.is_empty() || name.contains(['/', '\\', '.']) {
+            return Err(anyhow!("invalid platform name: {:?}", platform.name));
+        }
+        if PlatformKind::builtin().any(|kind| kind.to_string().eq_ignore_ascii_case(name)) {
+            return Err(anyhow!("platform name is reserved: {:?}", platform.name));
+        }
+        Regex::new(&platform.url_pattern)?;
+        let rules = &platform.rules;
+        let single = [
+            &rules.anchor_name,
+            &rules.anchor_avatar,
+            &rules.title,
+            &rules.viewer_count,
+            &rules.room_cover,
+            &rules.live_status,
+        ];
+        let streams = rules.streams.iter().map(|s| &s.rule);
+        for rule in single.into_iter().flatten().chain(streams) {
+            match rule {
+                ExtractRule::Regex(pattern) => {
+                    Regex::new(pattern)?;
+                }
+                ExtractRule::JsonPath(path) => {
+                    JsonPath::parse(path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 存放自定义平台定义文件的目录，支持 json 和 toml
+    pub fn definitions_dir() -> Result<PathBuf> {
+        let dir = config_dir()?.join("platforms");
+        if !dir.exists() {
+            std::fs::create_dir_all(&dir)?;
+        }
+        Ok(dir)
+    }
+
+    /// 读取定义目录中的文件和数据库中的定义，同名时以数据库中的为准
+    pub fn load_all() -> Result<Vec<CustomPlatform>> {
+        let mut platforms: Vec<CustomPlatform> = vec![];
+        for entry in std::fs::read_dir(definitions_dir()?)? {
+            let path = entry?.path();
+            match load_file(&path) {
+                Ok(Some(platform)) => platforms.push(platform),
+                Ok(None) => {}
+                Err(e) => eprintln!("Could not load custom platform {:?}: {}", path, e),
+            }
+        }
+        for platform in kv::custom_platform::get_all()? {
+            platforms.retain(|p| p.name != platform.name);
+            platforms.push(platform);
+        }
+        Ok(platforms)
+    }
+
+    /// 按扩展名解析定义文件，其它文件返回 None
+    fn load_file(path: &Path) -> Result<Option<CustomPlatform>> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        let platform = match extension.as_str() {
+            "json" => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+            "toml" => toml::from_str(&std::fs::read_to_string(path)?)?,
+            _ => return Ok(None),
+        };
+        Ok(Some(platform))
+    }
+
+    /// 定义有变化时调用，重新加载所有定义
+    pub fn reload() {
+        *RESOLVERS.write().unwrap() = Some(load_resolvers());
+    }
+
+    fn loaded() -> Vec<Arc<CustomResolver>> {
+        if let Some(resolvers) = RESOLVERS.read().unwrap().as_ref() {
+            return resolvers.clone();
+        }
+        let resolvers = load_resolvers();
+        *RESOLVERS.write().unwrap() = Some(resolvers.clone());
+        resolvers
+    }
+
+    fn load_resolvers() -> Vec<Arc<CustomResolver>> {
+        load_all()
+            .unwrap_or_else(|e| {
+                eprintln!("Could not load custom platforms: {}", e);
+                vec![]
+            })
+            .into_iter()
+            .filter_map(|platform| {
+                let name = platform.name.clone();
+                CustomResolver::new(platform)
+                    .map_err(|e| eprintln!("Invalid custom platform {}: {}", name, e))
+                    .ok()
+            })
+            .map(Arc::new)
+            .collect()
+    }
+
+    pub(super) fn resolvers() -> Vec<Arc<dyn Resolver>> {
+        loaded()
+            .into_iter()
+            .map(|r| r as Arc<dyn Resolver>)
+            .collect()
+    }
+
+    /// 这个自定义平台是否定义了分区的提取规则，只读取已经加载的定义
+    pub fn has_category_rule(name: &str) -> bool {
+        let resolvers = RESOLVERS.read().unwrap();
+        resolvers.as_ref().is_some_and(|resolvers| {
+            resolvers
+                .iter()
+                .any(|r| r.platform.name == name && r.platform.rules.category.is_some())
+        })
+    }
+
+    /// 网址或平台名匹配某个已加载的自定义平台时，返回对应的 PlatformKind
+    /// 只读取已经加载的定义，还没有加载时返回 None
+    pub fn match_kind(url: &str) -> Option<PlatformKind> {
+        let resolvers = RESOLVERS.read().unwrap();
+        resolvers
+            .as_ref()?
+            .iter()
+            .find(|r| r.matches(url) || r.platform.name.eq_ignore_ascii_case(url))
+            .map(|r| r.platform_kind())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_short_link() {
+        assert!(is_short_link("https://v.douyin.com/iYbS2fAK/"));
+        assert!(is_short_link(" HTTPS://V.DOUYIN.COM/iYbS2fAK/ "));
+        assert!(!is_short_link("https://live.douyin.com/790601393533"));
+    }
+
+    #[test]
+    fn test_douyin_canonical_url() {
+        let url =
+            Url::parse("https://live.douyin.com/790601393533?enter_from_merge=link_share").unwrap();
+        assert_eq!(
+            douyin::live_room_url(&url).as_deref(),
+            Some("https://live.douyin.com/790601393533")
+        );
+        let url = Url::parse(
+            "https://webcast.amemv.com/douyin/webcast/reflow/7384437436271471378?u_code=0&sec_user_id=MS4wLjAB",
+        )
+        .unwrap();
+        assert_eq!(
+            douyin::reflow_room_id(&url).as_deref(),
+            Some("7384437436271471378")
+        );
+        assert!(douyin::live_room_url(&url).is_none());
+    }
+
+    #[test]
+    fn test_guess_protocol() {
+        assert_eq!(
+            guess_protocol("https://example.com/live/1.m3u8?sign=abc"),
+            StreamingProtocol::Hls
+        );
+        assert_eq!(
+            guess_protocol("https://example.com/live/1.flv"),
+            StreamingProtocol::Flv
+        );
+    }
+
+    #[test]
+    fn test_custom_platform_definition() {
+        let platform: crate::model::CustomPlatform = toml::from_str(
+            r#"
+            name = "example"
+            urlPattern = '^https://live\.example\.com/(\d+)'
+            requestUrl = "https://api.example.com/room/$1"
+
+            [headers]
+            Referer = "https://live.example.com/"
+
+            [rules]
+            anchorName = { jsonPath = "$.data.anchor.name" }
+            title = { regex = '"title":"(.*?)"' }
+            liveStatus = { jsonPath = "$.data.status" }
+            liveValue = "1"
+            streams = [{ resolution = "origin", rule = { jsonPath = "$.data.streams[*].url" } }]
+            "#,
+        )
+        .unwrap();
+        custom::validate(&platform).unwrap();
+
+        let mut invalid = platform.clone();
+        invalid.name = "../example".into();
+        assert!(custom::validate(&invalid).is_err());
+        let mut invalid = platform.clone();
+        invalid.name = "douyin".into();
+        assert!(custom::validate(&invalid).is_err());
+        invalid.name = "Unknown".into();
+        assert!(custom::validate(&invalid).is_err());
+        let mut invalid = platform;
+        invalid.rules.title = Some(crate::model::ExtractRule::JsonPath("data.title[".into()));
+        assert!(custom::validate(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_direct_url() {
+        assert!(direct::is_direct_url("rtmp://example.com/live/room1"));
+        assert!(direct::is_direct_url(
+            "https://pull.example.com/live/room1.flv?sign=abc"
+        ));
+        assert!(direct::is_direct_url("http://example.com/hls/room1.m3u8"));
+        assert!(!direct::is_direct_url(
+            "https://live.douyin.com/790601393533"
+        ));
+        assert!(!direct::is_direct_url("not a url"));
+    }
+}
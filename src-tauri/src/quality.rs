@@ -0,0 +1,174 @@
+use crate::model::{QualityPolicy, QualityPreference, Stream, StreamQuality};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+static HEIGHT_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)(\d{3,4})p").unwrap());
+static BITRATE_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)(\d+(?:\.\d+)?)\s*(mbps|kbps|m)\b").unwrap());
+
+/// 各平台常见的清晰度名称对应的高度，按顺序匹配，长的名称放在前面
+const LABEL_HEIGHTS: &[(&str, u32)] = &[
+    ("4k", 2160),
+    ("2k", 1440),
+    ("full_hd", 1080),
+    ("蓝光", 1080),
+    ("超清", 720),
+    ("hd", 720),
+    ("高清", 540),
+    ("标清", 480),
+    ("sd1", 480),
+    ("sd2", 360),
+    ("流畅", 360),
+];
+
+/// 原画的各种叫法
+const ORIGIN_LABELS: &[&str] = &["原画", "origin", "origion", "source", "chunked"];
+
+/// 流的清晰度，解析器给出的优先，否则从名称推断
+pub fn quality(stream: &Stream) -> StreamQuality {
+    match &stream.quality {
+        Some(quality) => quality.clone(),
+        None => parse_label(&stream.resolution),
+    }
+}
+
+/// 从清晰度名称推断清晰度，例如 "1080p60"、"蓝光4M"、"FULL_HD1"、"原画"
+pub fn parse_label(label: &str) -> StreamQuality {
+    let lower = label.to_lowercase();
+    let origin = ORIGIN_LABELS.iter().any(|l| lower.contains(l));
+    let height = HEIGHT_REGEX
+        .captures(&lower)
+        .and_then(|c| c[1].parse().ok())
+        .or_else(|| {
+            LABEL_HEIGHTS
+                .iter()
+                .find(|(l, _)| lower.contains(l))
+                .map(|(_, h)| *h)
+        });
+    let bitrate = BITRATE_REGEX.captures(&lower).and_then(|c| {
+        let value: f64 = c[1].parse().ok()?;
+        let kbps = if c[2].starts_with('m') {
+            value * 1000.0
+        } else {
+            value
+        };
+        Some(kbps as u32)
+    });
+    StreamQuality {
+        height,
+        bitrate,
+        origin,
+    }
+}
+
+/// 排序用的键，原画最高，然后比较高度和码率
+fn rank(quality: &StreamQuality) -> (bool, u32, u32) {
+    (
+        quality.origin,
+        quality.height.unwrap_or(0),
+        quality.bitrate.unwrap_or(0),
+    )
+}
+
+/// 按照策略选择一个流，先按协议偏好的顺序筛选，协议都没有时不限制协议
+pub fn select_stream(streams: &[Stream], policy: &QualityPolicy) -> Option<Stream> {
+    for protocol in &policy.protocols {
+        let candidates: Vec<&Stream> = streams.iter().filter(|s| s.protocol == *protocol).collect();
+        if let Some(stream) = select_by_preference(&candidates, &policy.preference) {
+            return Some(stream.clone());
+        }
+    }
+    let candidates: Vec<&Stream> = streams.iter().collect();
+    select_by_preference(&candidates, &policy.preference).cloned()
+}
+
+fn select_by_preference<'a>(
+    candidates: &[&'a Stream],
+    preference: &QualityPreference,
+) -> Option<&'a Stream> {
+    let highest = || candidates.iter().max_by_key(|s| rank(&quality(s))).copied();
+    let lowest = || candidates.iter().min_by_key(|s| rank(&quality(s))).copied();
+    match preference {
+        QualityPreference::Highest => highest(),
+        QualityPreference::Lowest => lowest(),
+        QualityPreference::AtMost(max_height) => candidates
+            .iter()
+            .filter(|s| {
+                let quality = quality(s);
+                // 不知道高度的原画可能超过限制，不选
+                match quality.height {
+                    Some(height) => height <= *max_height,
+                    None => !quality.origin,
+                }
+            })
+            .max_by_key(|s| rank(&quality(s)))
+            .copied()
+            .or_else(lowest),
+        QualityPreference::Label(label) => candidates
+            .iter()
+            .find(|s| s.resolution == *label)
+            .copied()
+            .or_else(highest),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::StreamingProtocol;
+
+    fn stream(resolution: &str, protocol: StreamingProtocol) -> Stream {
+        Stream {
+            url: format!("https://example.com/{}", resolution),
+            resolution: resolution.into(),
+            protocol,
+            quality: None,
+            headers: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_parse_label() {
+        assert_eq!(parse_label("1080p60").height, Some(1080));
+        assert_eq!(parse_label("FULL_HD1").height, Some(1080));
+        assert_eq!(parse_label("蓝光4M").bitrate, Some(4000));
+        assert!(parse_label("原画").origin);
+        assert_eq!(parse_label("default"), StreamQuality::default());
+    }
+
+    #[test]
+    fn test_select_stream() {
+        let streams = vec![
+            stream("SD1", StreamingProtocol::Flv),
+            stream("FULL_HD1", StreamingProtocol::Flv),
+            stream("HD1", StreamingProtocol::Hls),
+            stream("原画", StreamingProtocol::Hls),
+        ];
+        let policy = |preference, protocols| QualityPolicy {
+            preference,
+            protocols,
+        };
+
+        let selected = select_stream(&streams, &policy(QualityPreference::Highest, vec![]));
+        assert_eq!(selected.unwrap().resolution, "原画");
+
+        let selected = select_stream(
+            &streams,
+            &policy(QualityPreference::Highest, vec![StreamingProtocol::Flv]),
+        );
+        assert_eq!(selected.unwrap().resolution, "FULL_HD1");
+
+        let selected = select_stream(&streams, &policy(QualityPreference::AtMost(720), vec![]));
+        assert_eq!(selected.unwrap().resolution, "HD1");
+
+        // 名称变了也不会掉到第一个流
+        let selected = select_stream(
+            &streams,
+            &policy(
+                QualityPreference::Label("蓝光".into()),
+                vec![StreamingProtocol::Flv],
+            ),
+        );
+        assert_eq!(selected.unwrap().resolution, "FULL_HD1");
+    }
+}
@@ -1,6 +1,7 @@
-use anyhow::Result;
-use reqwest::header::HeaderMap;
+use anyhow::{anyhow, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, LOCATION};
 use reqwest::{Body, Response};
+use std::collections::HashMap;
 
 pub async fn get(url: &str, headers: HeaderMap) -> Result<Response> {
     let client = reqwest::Client::new();
@@ -26,3 +27,77 @@ pub async fn try_get(url: &str, headers: HeaderMap, timeout: u64) -> Result<Resp
     let resp = req.send().await?;
     Ok(resp)
 }
+
+/// 前端解析器使用的请求，开启了系统代理时走系统代理，和前端原来的请求方式一致
+/// body 为空时发送 GET，否则发送 POST
+pub async fn send(
+    url: &str,
+    headers: HeaderMap,
+    body: Option<String>,
+    timeout: u64,
+) -> Result<Response> {
+    let mut builder = reqwest::Client::builder().timeout(std::time::Duration::from_secs(timeout));
+    match sysproxy::Sysproxy::get_system_proxy() {
+        Ok(proxy) if proxy.enable => {
+            let address = format!("http://{}:{}", proxy.host, proxy.port);
+            builder = builder.proxy(reqwest::Proxy::all(address)?);
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("can not get system http proxy: {}", e),
+    }
+    let client = builder.build()?;
+    let req = match body {
+        Some(body) => client.post(url).body(body),
+        None => client.get(url),
+    };
+    let resp = req.headers(headers).send().await?;
+    Ok(resp)
+}
+
+/// 不自动跳转，手动跟随重定向链，最多跟随 max_hops 次
+/// 返回经过的所有地址，第一个是传入的 url，最后一个是最终地址
+pub async fn follow_redirects(
+    url: &str,
+    headers: HeaderMap,
+    max_hops: usize,
+) -> Result<Vec<String>> {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(std::time::Duration::from_secs(10))
+        .build()?;
+    let mut chain = vec![url.to_string()];
+    let mut current = reqwest::Url::parse(url)?;
+    loop {
+        let resp = client
+            .get(current.clone())
+            .headers(headers.clone())
+            .send()
+            .await?;
+        if !resp.status().is_redirection() {
+            return Ok(chain);
+        }
+        if chain.len() > max_hops {
+            return Err(anyhow!("too many redirects: {}", url));
+        }
+        let location = resp
+            .headers()
+            .get(LOCATION)
+            .ok_or_else(|| anyhow!("redirect without location: {}", current))?
+            .to_str()?;
+        // location 可能是相对地址
+        current = current.join(location)?;
+        chain.push(current.to_string());
+    }
+}
+
+/// 把字符串键值对转换为 HeaderMap
+pub fn header_map(headers: &HashMap<String, String>) -> Result<HeaderMap> {
+    let mut header_map = HeaderMap::new();
+    for (k, v) in headers {
+        header_map.insert(
+            HeaderName::from_bytes(k.as_bytes())?,
+            HeaderValue::from_str(v)?,
+        );
+    }
+    Ok(header_map)
+}
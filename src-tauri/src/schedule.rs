@@ -0,0 +1,182 @@
+use crate::{
+    kv,
+    model::{AppConfig, PlanSchedule, RecordingPlan},
+};
+use anyhow::Result;
+use chrono::{DateTime, Local, TimeZone, Timelike, Utc};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+
+/// 用于预测的最近开播次数
+const MAX_SAMPLES: usize = 30;
+/// 两次录制开始时间相差不到这么多分钟，认为是同一场直播（中途断流重新录制）
+const SESSION_GAP_MINUTES: i64 = 120;
+/// 预测的开播时刻前后这么多分钟内，使用最短的检查间隔
+const WINDOW_MINUTES: i64 = 30;
+const DAY_MINUTES: i64 = 24 * 60;
+
+// 每个计划下一次检查的时间，毫秒时间戳，程序启动后所有计划都会立刻检查一次
+static NEXT_CHECK: Lazy<DashMap<String, i64>> = Lazy::new(DashMap::new);
+
+/// 从录制开始时间得到每场直播的开播时刻，本地时间从零点开始的分钟数
+fn start_minutes(start_times: &[i64]) -> Vec<i64> {
+    let mut sessions: Vec<i64> = vec![];
+    for &start_time in start_times {
+        match sessions.last() {
+            Some(last) if start_time - last < SESSION_GAP_MINUTES * 60_000 => {}
+            _ => sessions.push(start_time),
+        }
+    }
+    let skip = sessions.len().saturating_sub(MAX_SAMPLES);
+    sessions
+        .into_iter()
+        .skip(skip)
+        .filter_map(|millis| Local.timestamp_millis_opt(millis).single())
+        .map(|time| (time.hour() * 60 + time.minute()) as i64)
+        .collect()
+}
+
+fn minute_of_day(now: &DateTime<Local>) -> i64 {
+    (now.hour() * 60 + now.minute()) as i64
+}
+
+/// 距离最近的开播时刻有多少分钟，之前和之后都算
+fn distance(minutes: &[i64], now: i64) -> Option<i64> {
+    minutes
+        .iter()
+        .map(|m| {
+            let after = (m - now).rem_euclid(DAY_MINUTES);
+            let before = (now - m).rem_euclid(DAY_MINUTES);
+            after.min(before)
+        })
+        .min()
+}
+
+/// 预测的下一次开播时间，毫秒时间戳；正处在开播时刻附近时，返回最近的那个开播时刻
+fn predict_next_live(minutes: &[i64], now: &DateTime<Local>) -> Option<i64> {
+    let now_minute = minute_of_day(now);
+    let delta = minutes
+        .iter()
+        .map(|m| {
+            let before = (now_minute - m).rem_euclid(DAY_MINUTES);
+            if before <= WINDOW_MINUTES {
+                -before
+            } else {
+                (m - now_minute).rem_euclid(DAY_MINUTES)
+            }
+        })
+        .min()?;
+    // 只保留到分钟，毫秒部分也要去掉，否则预测的时间会带上当前时刻的毫秒数
+    let now_millis = (now.timestamp() - now.second() as i64) * 1000;
+    Some(now_millis + delta * 60_000)
+}
+
+/// 下一次检查的间隔秒数：开播时刻附近最短，离得越远间隔越长，但不会跳过下一个开播时刻
+fn check_interval(minutes: &[i64], now_minute: i64, config: &AppConfig) -> u64 {
+    let min = config.poll_min_interval.max(1);
+    let max = config.poll_max_interval.max(min);
+    let interval = match distance(minutes, now_minute) {
+        // 没有历史记录时使用固定的检查间隔
+        None => config.live_info_check_interval,
+        Some(distance) if distance <= WINDOW_MINUTES => min,
+        // 离窗口还有多远，就按它的四分之一等待
+        Some(distance) => ((distance - WINDOW_MINUTES) * 60 / 4) as u64,
+    };
+    interval.clamp(min, max)
+}
+
+/// 从计划中选出已经到检查时间的，并安排它们的下一次检查
+pub fn due_plans(plans: Vec<RecordingPlan>) -> Result<Vec<RecordingPlan>> {
+    let config = kv::config::get()?;
+    let now = Local::now();
+    let now_millis = now.timestamp_millis();
+    let mut due = vec![];
+    for plan in plans {
+        let next_check = NEXT_CHECK.get(&plan.url).map(|t| *t).unwrap_or(0);
+        if next_check > now_millis {
+            continue;
+        }
+        let minutes = start_minutes(&kv::history::get_start_times(&plan.url)?);
+        let interval = check_interval(&minutes, minute_of_day(&now), &config);
+        NEXT_CHECK.insert(plan.url.clone(), now_millis + interval as i64 * 1000);
+        due.push(plan);
+    }
+    Ok(due)
+}
+
+/// 让计划在下一轮立刻检查
+pub fn check_now(url: &str) {
+    NEXT_CHECK.remove(url);
+}
+
+/// 计划的检查安排和预测的开播时间
+pub fn plan_schedule(url: &str) -> Result<PlanSchedule> {
+    let minutes = start_minutes(&kv::history::get_start_times(url)?);
+    let next_check_at = NEXT_CHECK
+        .get(url)
+        .map(|t| *t)
+        .unwrap_or_else(|| Utc::now().timestamp_millis());
+    Ok(PlanSchedule {
+        url: url.into(),
+        next_check_at,
+        predicted_live_at: predict_next_live(&minutes, &Local::now()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_interval() {
+        let config = AppConfig {
+            poll_min_interval: 30,
+            poll_max_interval: 600,
+            ..Default::default()
+        };
+        // 20:00 开播
+        let minutes = [20 * 60];
+        assert_eq!(check_interval(&minutes, 19 * 60 + 45, &config), 30);
+        assert_eq!(check_interval(&minutes, 20 * 60 + 20, &config), 30);
+        // 19:00 距离窗口 30 分钟，等待 7.5 分钟
+        assert_eq!(check_interval(&minutes, 19 * 60, &config), 450);
+        assert_eq!(check_interval(&minutes, 3 * 60, &config), 600);
+        assert_eq!(check_interval(&[], 3 * 60, &config), 60);
+    }
+
+    #[test]
+    fn test_predict_next_live() {
+        let minutes = [20 * 60, 8 * 60];
+        let now = Local.with_ymd_and_hms(2024, 6, 1, 19, 0, 30).unwrap();
+        let expected = Local.with_ymd_and_hms(2024, 6, 1, 20, 0, 0).unwrap();
+        assert_eq!(
+            predict_next_live(&minutes, &now),
+            Some(expected.timestamp_millis())
+        );
+        // 跨过零点
+        let now = Local.with_ymd_and_hms(2024, 6, 1, 21, 0, 0).unwrap();
+        let expected = Local.with_ymd_and_hms(2024, 6, 2, 8, 0, 0).unwrap();
+        assert_eq!(
+            predict_next_live(&minutes, &now),
+            Some(expected.timestamp_millis())
+        );
+        // 当前时刻带有毫秒时，预测的时间仍然是整分钟
+        let now = Local.with_ymd_and_hms(2024, 6, 1, 19, 0, 30).unwrap()
+            + chrono::Duration::milliseconds(250);
+        let expected = Local.with_ymd_and_hms(2024, 6, 1, 20, 0, 0).unwrap();
+        assert_eq!(
+            predict_next_live(&minutes, &now),
+            Some(expected.timestamp_millis())
+        );
+        assert_eq!(predict_next_live(&[], &now), None);
+    }
+
+    #[test]
+    fn test_start_minutes() {
+        let start = Local.with_ymd_and_hms(2024, 6, 1, 20, 0, 0).unwrap();
+        let start = start.timestamp_millis();
+        // 十分钟后断流重新录制，不算新的一场
+        let minutes = start_minutes(&[start, start + 600_000, start + 86_400_000]);
+        assert_eq!(minutes, vec![20 * 60, 20 * 60]);
+    }
+}
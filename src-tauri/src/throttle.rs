@@ -0,0 +1,164 @@
+use crate::{kv, model::PlatformKind};
+use anyhow::{anyhow, Result};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use reqwest::StatusCode;
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::Semaphore;
+
+/// 第一次被限流后等待的时间，之后每次翻倍
+const BACKOFF_BASE: Duration = Duration::from_secs(5);
+/// 最长等待时间
+const BACKOFF_MAX: Duration = Duration::from_secs(600);
+
+// 每个平台一个限流器，键是平台名
+static LIMITERS: Lazy<DashMap<String, Arc<Limiter>>> = Lazy::new(DashMap::new);
+
+/// 单个平台的限流状态
+struct Limiter {
+    // 同时进行的请求数
+    semaphore: Arc<Semaphore>,
+    // 下一个请求最早可以开始的时间
+    next_slot: Mutex<Instant>,
+    // 连续被限流的次数，以及退避结束的时间
+    backoff: Mutex<(u32, Option<Instant>)>,
+}
+
+impl Limiter {
+    fn new(concurrency: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(concurrency.max(1))),
+            next_slot: Mutex::new(Instant::now()),
+            backoff: Mutex::new((0, None)),
+        }
+    }
+
+    /// 还需要退避多久，不在退避中返回 None
+    fn backoff_remaining(&self) -> Option<Duration> {
+        let (_, until) = *self.backoff.lock().unwrap();
+        until.and_then(|until| until.checked_duration_since(Instant::now()))
+    }
+
+    /// 占一个请求时间，返回需要等待多久
+    fn reserve_slot(&self, min_interval: Duration) -> Duration {
+        let mut next_slot = self.next_slot.lock().unwrap();
+        let now = Instant::now();
+        let start = (*next_slot).max(now);
+        *next_slot = start + min_interval;
+        start - now
+    }
+
+    fn on_success(&self) {
+        *self.backoff.lock().unwrap() = (0, None);
+    }
+
+    fn on_throttled(&self) -> Duration {
+        let mut backoff = self.backoff.lock().unwrap();
+        let failures = backoff.0 + 1;
+        let wait = BACKOFF_BASE
+            .saturating_mul(2u32.saturating_pow(failures - 1))
+            .min(BACKOFF_MAX);
+        *backoff = (failures, Some(Instant::now() + wait));
+        wait
+    }
+}
+
+fn limiter(platform_kind: &PlatformKind) -> Result<Arc<Limiter>> {
+    let key = platform_kind.to_string();
+    if let Some(limiter) = LIMITERS.get(&key) {
+        return Ok(limiter.clone());
+    }
+    // 并发数在第一次使用时确定，修改配置后重启生效
+    let concurrency = kv::config::get()?.resolver_concurrency;
+    let limiter = LIMITERS
+        .entry(key)
+        .or_insert_with(|| Arc::new(Limiter::new(concurrency)));
+    Ok(limiter.clone())
+}
+
+/// 平台返回 429 或 403 时认为被限流了，404 之类的其它错误和限流无关，不需要退避
+fn is_throttled(e: &anyhow::Error) -> bool {
+    e.chain()
+        .filter_map(|e| e.downcast_ref::<reqwest::Error>())
+        .filter_map(|e| e.status())
+        .any(is_throttled_status)
+}
+
+fn is_throttled_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::FORBIDDEN
+}
+
+/// 在平台的限流规则下执行请求：限制并发数和请求间隔，被限流后指数退避，退避期间直接返回错误
+pub async fn run<T, F, Fut>(platform_kind: &PlatformKind, f: F) -> Result<T>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let limiter = limiter(platform_kind)?;
+    if let Some(remaining) = limiter.backoff_remaining() {
+        return Err(anyhow!(
+            "{} is rate limited, retry in {} seconds",
+            platform_kind,
+            remaining.as_secs()
+        ));
+    }
+    let _permit = limiter.semaphore.clone().acquire_owned().await?;
+    let min_interval = Duration::from_millis(kv::config::get()?.resolver_min_interval);
+    tokio::time::sleep(limiter.reserve_slot(min_interval)).await;
+    let result = f().await;
+    match &result {
+        Ok(_) => limiter.on_success(),
+        Err(e) if is_throttled(e) => {
+            let wait = limiter.on_throttled();
+            eprintln!(
+                "{} is rate limited, back off for {} seconds: {}",
+                platform_kind,
+                wait.as_secs(),
+                e
+            );
+        }
+        Err(_) => {}
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let limiter = Limiter::new(1);
+        assert!(limiter.backoff_remaining().is_none());
+        assert_eq!(limiter.on_throttled(), Duration::from_secs(5));
+        assert_eq!(limiter.on_throttled(), Duration::from_secs(10));
+        assert!(limiter.backoff_remaining().is_some());
+        for _ in 0..20 {
+            limiter.on_throttled();
+        }
+        assert_eq!(limiter.on_throttled(), BACKOFF_MAX);
+        limiter.on_success();
+        assert!(limiter.backoff_remaining().is_none());
+    }
+
+    #[test]
+    fn test_throttled_status() {
+        assert!(is_throttled_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_throttled_status(StatusCode::FORBIDDEN));
+        assert!(!is_throttled_status(StatusCode::NOT_FOUND));
+        assert!(!is_throttled_status(StatusCode::BAD_REQUEST));
+        assert!(!is_throttled_status(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    #[test]
+    fn test_reserve_slot() {
+        let limiter = Limiter::new(1);
+        let interval = Duration::from_secs(1);
+        assert_eq!(limiter.reserve_slot(interval), Duration::ZERO);
+        assert!(limiter.reserve_slot(interval) > Duration::from_millis(900));
+    }
+}
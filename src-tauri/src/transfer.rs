@@ -0,0 +1,402 @@
+use crate::{
+    kv,
+    model::{
+        ImportEntry, ImportReport, ImportResult, PlanFormat, PlatformKind, QualityPolicy,
+        QualityPreference, RecordingPlan, StreamingProtocol,
+    },
+    platform,
+};
+use anyhow::{anyhow, Result};
+use std::collections::HashSet;
+
+/// csv 的列，导出时按这个顺序，导入时没有表头也按这个顺序
+const CSV_COLUMNS: &[&str] = &[
+    "url",
+    "displayName",
+    "enabled",
+    "streamProtocol",
+    "streamResolution",
+    "useProxy",
+    "autoConvertToMp4",
+    "deleteOriginalFile",
+    "tags",
+];
+
+/// DouyinLiveRecorder 的清晰度名称和对应的最大高度，原画不限制
+const URL_CONFIG_QUALITIES: &[(&str, Option<u32>)] = &[
+    ("原画", None),
+    ("蓝光", Some(1080)),
+    ("超清", Some(720)),
+    ("高清", Some(540)),
+    ("标清", Some(480)),
+    ("流畅", Some(360)),
+];
+
+/// 文件中的一条计划，行号从 1 开始，json 格式是数组下标加 1
+struct Parsed {
+    line: usize,
+    url: String,
+    plan: Result<RecordingPlan>,
+}
+
+/// 导入计划，dry_run 为 true 时只检查不保存；重复的网址（已有的计划或文件中前面出现过的）会跳过
+/// 有效的计划在最后用一个事务保存，不会只导入一部分
+pub async fn import(content: &str, format: PlanFormat, dry_run: bool) -> Result<ImportReport> {
+    let mut seen: HashSet<String> = kv::plan::get_all()?
+        .into_iter()
+        .map(|plan| plan.url)
+        .collect();
+    let mut entries = vec![];
+    let mut added = vec![];
+    for parsed in parse(content, format)? {
+        let (url, result) = match parsed.plan {
+            Ok(plan) => match check(plan).await {
+                Ok(plan) if !seen.insert(plan.url.clone()) => (plan.url, ImportResult::Duplicate),
+                Ok(plan) => {
+                    let url = plan.url.clone();
+                    added.push(plan);
+                    (url, ImportResult::Added)
+                }
+                Err(e) => (parsed.url, ImportResult::Error(e.to_string())),
+            },
+            Err(e) => (parsed.url, ImportResult::Error(e.to_string())),
+        };
+        entries.push(ImportEntry {
+            line: parsed.line,
+            url,
+            result,
+        });
+    }
+    if !dry_run && !added.is_empty() {
+        kv::plan::add_all(&added)?;
+    }
+    Ok(ImportReport { dry_run, entries })
+}
+
+// 展开短链接，检查平台和计划设置
+async fn check(mut plan: RecordingPlan) -> Result<RecordingPlan> {
+    plan.url = platform::canonicalize_url(&plan.url).await?;
+    if PlatformKind::from(&plan.url) == PlatformKind::Unknown {
+        return Err(anyhow!("Unknown platform"));
+    }
+    if let Some(live_info) = plan.live_info.as_mut() {
+        live_info.url = plan.url.clone();
+    }
+    // 和 add_plan 一样，外部解析器不支持的网址不导入
+    platform::check_supported(&plan.url).await?;
+    plan.validate()?;
+    Ok(plan)
+}
+
+fn parse(content: &str, format: PlanFormat) -> Result<Vec<Parsed>> {
+    match format {
+        PlanFormat::Text => Ok(lines(content)
+            .map(|(line, url)| Parsed {
+                line,
+                url: url.into(),
+                plan: Ok(RecordingPlan::new_with_url(url)),
+            })
+            .collect()),
+        PlanFormat::Csv => Ok(parse_csv(content)),
+        PlanFormat::UrlConfig => Ok(parse_url_config(content)),
+        PlanFormat::Json => {
+            // 先解析成数组，每一项单独转换，一项有问题不影响其它项，并且能报告是哪一项
+            let values: Vec<serde_json::Value> = serde_json::from_str(content)?;
+            Ok(values
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| Parsed {
+                    line: i + 1,
+                    url: value["url"].as_str().unwrap_or_default().into(),
+                    plan: serde_json::from_value(value).map_err(Into::into),
+                })
+                .collect())
+        }
+    }
+}
+
+// 非空且不是注释的行，带行号
+fn lines(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+}
+
+fn parse_csv(content: &str) -> Vec<Parsed> {
+    let mut columns: Vec<String> = CSV_COLUMNS.iter().map(|c| c.to_string()).collect();
+    let mut result = vec![];
+    for (line, text) in lines(content) {
+        let fields = split_csv_line(text);
+        // 第一列是 url 的行是表头，按表头的顺序读取
+        if fields
+            .first()
+            .is_some_and(|f| f.eq_ignore_ascii_case("url"))
+        {
+            columns = fields;
+            continue;
+        }
+        let get = |name: &str| {
+            columns
+                .iter()
+                .position(|c| c.eq_ignore_ascii_case(name))
+                .and_then(|i| fields.get(i))
+                .map(|f| f.trim())
+                .filter(|f| !f.is_empty())
+        };
+        let url = get("url").unwrap_or_default().to_string();
+        let plan = csv_plan(&url, get);
+        result.push(Parsed { line, url, plan });
+    }
+    result
+}
+
+fn csv_plan<'a>(url: &str, get: impl Fn(&str) -> Option<&'a str>) -> Result<RecordingPlan> {
+    if url.is_empty() {
+        return Err(anyhow!("url is empty"));
+    }
+    let bool_field = |name: &str| -> Result<Option<bool>> {
+        match get(name).map(|v| v.to_lowercase()) {
+            None => Ok(None),
+            Some(v) if ["true", "1", "yes"].contains(&v.as_str()) => Ok(Some(true)),
+            Some(v) if ["false", "0", "no"].contains(&v.as_str()) => Ok(Some(false)),
+            Some(v) => Err(anyhow!("invalid {}: {}", name, v)),
+        }
+    };
+    let mut plan = RecordingPlan::new_with_url(url);
+    plan.display_name = get("displayName").map(Into::into);
+    plan.enabled = bool_field("enabled")?.unwrap_or(true);
+    if let Some(protocol) = get("streamProtocol") {
+        plan.stream_protocol = match protocol.to_lowercase().as_str() {
+            "flv" => StreamingProtocol::Flv,
+            "hls" => StreamingProtocol::Hls,
+            _ => return Err(anyhow!("invalid streamProtocol: {}", protocol)),
+        };
+    }
+    plan.stream_resolution = get("streamResolution").unwrap_or_default().into();
+    plan.option.use_proxy = get("useProxy").map(Into::into);
+    plan.option.auto_convert_to_mp4 = bool_field("autoConvertToMp4")?.unwrap_or(false);
+    plan.option.delete_original_file = bool_field("deleteOriginalFile")?.unwrap_or(false);
+    // 多个标签用分号分隔
+    plan.tags = get("tags")
+        .unwrap_or_default()
+        .split(';')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(Into::into)
+        .collect();
+    Ok(plan)
+}
+
+// DouyinLiveRecorder 的 URL_config.ini，每行是 "清晰度,网址,主播: 名字"，清晰度和名字可以省略
+// 中英文逗号都可以，# 开头的网址是暂停录制的房间，导入为停用的计划
+fn parse_url_config(content: &str) -> Vec<Parsed> {
+    let mut result = vec![];
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        let (enabled, text) = match line.strip_prefix('#') {
+            Some(text) => (false, text.trim()),
+            None => (true, line),
+        };
+        let fields: Vec<&str> = text.split([',', '，']).map(str::trim).collect();
+        let Some(url_index) = fields.iter().position(|f| f.contains("://")) else {
+            // 没有网址的是空行或者普通注释
+            continue;
+        };
+        let url = fields[url_index].to_string();
+        let quality = fields[..url_index].first().copied().unwrap_or_default();
+        let name = fields.get(url_index + 1).copied().unwrap_or_default();
+        let mut plan = RecordingPlan::new_with_url(&url);
+        plan.enabled = enabled;
+        plan.quality_policy = Some(QualityPolicy {
+            preference: url_config_preference(quality),
+            protocols: vec![StreamingProtocol::Flv],
+        });
+        let name = name
+            .trim_start_matches("主播")
+            .trim_start_matches([':', '：'])
+            .trim();
+        plan.display_name = (!name.is_empty()).then(|| name.to_string());
+        result.push(Parsed {
+            line: i + 1,
+            url,
+            plan: Ok(plan),
+        });
+    }
+    result
+}
+
+fn url_config_preference(quality: &str) -> QualityPreference {
+    match URL_CONFIG_QUALITIES
+        .iter()
+        .find(|(name, _)| *name == quality)
+    {
+        Some((_, Some(height))) => QualityPreference::AtMost(*height),
+        _ => QualityPreference::Highest,
+    }
+}
+
+fn url_config_quality(plan: &RecordingPlan) -> &'static str {
+    let height = match plan.quality_policy().preference {
+        QualityPreference::AtMost(height) => height,
+        QualityPreference::Lowest => 0,
+        _ => return "原画",
+    };
+    // 取不超过限制的最高一档
+    URL_CONFIG_QUALITIES
+        .iter()
+        .find(|(_, h)| h.is_some_and(|h| h <= height))
+        .map(|(name, _)| *name)
+        .unwrap_or("流畅")
+}
+
+// 分割一行 csv，支持双引号包起来的字段和其中的 "" 转义
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.into()
+    }
+}
+
+/// 按格式导出所有计划
+pub fn export(format: PlanFormat) -> Result<String> {
+    let plans = kv::plan::get_all()?;
+    let content = match format {
+        PlanFormat::Text => plans
+            .iter()
+            .map(|plan| plan.url.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        PlanFormat::Csv => {
+            let mut rows = vec![CSV_COLUMNS.join(",")];
+            for plan in &plans {
+                let row = [
+                    plan.url.clone(),
+                    plan.display_name.clone().unwrap_or_default(),
+                    plan.enabled.to_string(),
+                    format!("{:?}", plan.stream_protocol),
+                    plan.stream_resolution.clone(),
+                    plan.option.use_proxy.clone().unwrap_or_default(),
+                    plan.option.auto_convert_to_mp4.to_string(),
+                    plan.option.delete_original_file.to_string(),
+                    plan.tags.join(";"),
+                ];
+                rows.push(
+                    row.iter()
+                        .map(|f| csv_field(f))
+                        .collect::<Vec<_>>()
+                        .join(","),
+                );
+            }
+            rows.join("\n")
+        }
+        PlanFormat::Json => serde_json::to_string_pretty(&plans)?,
+        PlanFormat::UrlConfig => plans
+            .iter()
+            .map(|plan| {
+                let mut line = format!("{},{}", url_config_quality(plan), plan.url);
+                if let Some(name) = &plan.display_name {
+                    line.push_str(&format!(",主播: {}", name));
+                }
+                if !plan.enabled {
+                    line.insert(0, '#');
+                }
+                line
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_csv_line() {
+        assert_eq!(split_csv_line("a,,b"), vec!["a", "", "b"]);
+        assert_eq!(
+            split_csv_line(r#"a,"b, ""c""",d"#),
+            vec!["a", r#"b, "c""#, "d"]
+        );
+        assert_eq!(split_csv_line(&csv_field("x,\"y\"")), vec!["x,\"y\""]);
+    }
+
+    #[test]
+    fn test_parse_url_config() {
+        let content = "# 这是注释\n\
+            超清,https://live.douyin.com/1,主播: 张三\n\
+            #原画，https://live.bilibili.com/2\n\
+            https://www.huya.com/3\n";
+        let parsed = parse(content, PlanFormat::UrlConfig).unwrap();
+        assert_eq!(parsed.len(), 3);
+        let plan = parsed[0].plan.as_ref().unwrap();
+        assert!(plan.enabled);
+        assert_eq!(plan.display_name.as_deref(), Some("张三"));
+        assert_eq!(url_config_quality(plan), "超清");
+        let plan = parsed[1].plan.as_ref().unwrap();
+        assert!(!plan.enabled);
+        assert_eq!(plan.url, "https://live.bilibili.com/2");
+        assert_eq!(url_config_quality(plan), "原画");
+        assert_eq!(parsed[2].line, 4);
+        assert_eq!(parsed[2].plan.as_ref().unwrap().display_name, None);
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let plan = RecordingPlan::new_with_url("https://live.douyin.com/1");
+        let content = serde_json::to_string(&vec![
+            serde_json::to_value(&plan).unwrap(),
+            serde_json::json!({ "url": "https://live.douyin.com/2", "enabled": "yes" }),
+        ])
+        .unwrap();
+        let parsed = parse(&content, PlanFormat::Json).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].plan.as_ref().unwrap().url, plan.url);
+        assert_eq!(parsed[1].line, 2);
+        assert_eq!(parsed[1].url, "https://live.douyin.com/2");
+        assert!(parsed[1].plan.is_err());
+        // 不是数组时整个文件都无法导入
+        assert!(parse("{}", PlanFormat::Json).is_err());
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let content = "url,enabled,streamProtocol\n\
+            https://live.douyin.com/1,false,hls\n\
+            # comment\n\
+            ,true,flv\n\
+            https://live.douyin.com/2,maybe,flv\n";
+        let parsed = parse(content, PlanFormat::Csv).unwrap();
+        assert_eq!(parsed.len(), 3);
+        let plan = parsed[0].plan.as_ref().unwrap();
+        assert!(!plan.enabled);
+        assert_eq!(plan.stream_protocol, StreamingProtocol::Hls);
+        assert_eq!(parsed[1].line, 4);
+        assert!(parsed[1].plan.is_err());
+        assert!(parsed[2].plan.is_err());
+    }
+}
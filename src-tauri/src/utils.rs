@@ -1,6 +1,6 @@
 use crate::{kv, model::PlatformKind};
 use anyhow::Result;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// 生成文件名
 pub fn generate_filename(anchor_name: &str) -> String {
@@ -19,19 +19,25 @@ pub async fn generate_path(platform_kind: &PlatformKind, anchor_name: &str) -> R
     let path = kv::config::get()?.save_path;
     let path = PathBuf::from(path);
     let platform_name = platform_kind.to_string();
-    let anchor_name = anchor_name
+    let full_path = path
+        .join(platform_name)
+        .join(anchor_folder_name(anchor_name));
+    Ok(full_path
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("Could not convert path to string: {:?}", full_path))?
+        .to_string())
+}
+
+/// 主播文件夹的名字，扫描录制文件时用它和主播名对应
+pub fn anchor_folder_name(anchor_name: &str) -> String {
+    anchor_name
         .chars()
         .filter(|c| c.is_alphanumeric())
         .collect::<String>()
         // 去掉所有的 . / 以及 \，避免路径问题
         .replace(".", "")
         .replace("/", "")
-        .replace("\\", "");
-    let full_path = path.join(platform_name).join(anchor_name);
-    Ok(full_path
-        .to_str()
-        .ok_or_else(|| anyhow::anyhow!("Could not convert path to string: {:?}", full_path))?
-        .to_string())
+        .replace("\\", "")
 }
 
 pub async fn generate_path_and_filename(
@@ -42,3 +48,54 @@ pub async fn generate_path_and_filename(
     let path = generate_path(platform_kind, anchor_name).await?;
     Ok((path, filename))
 }
+
+/// 移动文件，不在同一个设备上时先复制再删除原文件
+pub fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    match std::fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            copy_file(from, to)?;
+            std::fs::remove_file(from)?;
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 复制文件，先复制到同目录下的临时文件再改名，中断时不会在目标位置留下不完整的文件
+pub fn copy_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut name = to
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("invalid path: {}", to.display()))?
+        .to_os_string();
+    name.push(".part");
+    let part = to.with_file_name(name);
+    if let Err(e) = std::fs::copy(from, &part).and_then(|_| std::fs::rename(&part, to)) {
+        std::fs::remove_file(&part).ok();
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// 测试用的临时目录，每次调用都是一个新的空目录
+#[cfg(test)]
+pub fn test_dir(name: &str) -> PathBuf {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let dir = std::env::temp_dir().join(format!(
+        "liveship-test-{}-{}-{}",
+        name,
+        std::process::id(),
+        nanos
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
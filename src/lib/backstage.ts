@@ -5,21 +5,21 @@ import { getLiveInfoForPlatform } from './utils';
 export function checkPlanLoop() {
 	// 进入时立刻检查一次计划
 	checkPlans();
-	// 然后每 60 秒检查一次
+	// 然后每 10 秒询问一次后端，每个计划的检查间隔由后端根据主播的开播规律决定
 	setInterval(() => {
 		checkPlans();
-	}, 60000);
+	}, 10000);
 }
 
 function checkPlans() {
-	// 首先，获取有计划，但未在录制的任务
-	invoke('get_plans_not_recording')
+	// 首先，获取有计划，未在录制，并且到了检查时间的任务
+	invoke('get_plans_to_check')
 		.then((data) => {
 			let plans: RecordingPlan[] = data as RecordingPlan[];
 			if (plans.length == 0) {
 				return;
 			}
-			// 遍历计划，检查主播是否已开播
+			// 遍历计划，检查主播是否已开播，解析器的请求都经过后端，受每个平台的限流规则约束
 			plans.forEach(async (plan) => {
 				try {
 					let liveInfo = await getLiveInfoForPlatform(plan.url);
@@ -29,20 +29,9 @@ function checkPlans() {
 							console.error('no stream found in live info: ', liveInfo);
 							return;
 						}
-						// 根据 plan 里的分辨率和类型，选择一样的流，如果没有，选择一个最接近的
-						let stream = liveInfo.streams[0];
-						liveInfo.streams.forEach((s) => {
-							if (s.resolution === plan.streamResolution && s.protocol === plan.streamProtocol) {
-								stream = s;
-							}
-						});
 						console.log('start record: ', liveInfo.anchorName, plan.url);
-						// 开始录制
-						invoke('start_record', {
-							autoRecord: false,
-							stream,
-							liveInfo
-						});
+						// 开始录制，由后端按照计划的选流策略选择流
+						invoke('start_plan_record', { liveInfo });
 					}
 				} catch (e) {
 					console.error('check plan failed: ', e);
@@ -7,7 +7,9 @@ export interface LiveInfo {
 	viewerCount: string;
 	roomCover: string;
 	streams: Array<Stream>;
-	platformKind: PlatformKind;
+	platformKind: PlatformKind | { Custom: string };
+	category?: string;
+	updatedAt?: number;
 }
 
 export enum PlatformKind {
@@ -20,6 +22,8 @@ export enum PlatformKind {
 	Bilibili = 'Bilibili',
 	Twitch = 'Twitch',
 	Youtube = 'Youtube',
+	Direct = 'Direct',
+	External = 'External',
 	Unknown = 'Unknown'
 }
 
@@ -27,6 +31,25 @@ export interface Stream {
 	url: string;
 	resolution: string;
 	protocol: StreamingProtocol;
+	quality?: StreamQuality;
+	headers?: { [key: string]: string };
+}
+
+export interface StreamQuality {
+	height?: number;
+	bitrate?: number;
+	origin: boolean;
+}
+
+export type QualityPreference =
+	| 'Highest'
+	| 'Lowest'
+	| { AtMost: number }
+	| { Label: string };
+
+export interface QualityPolicy {
+	preference: QualityPreference;
+	protocols: StreamingProtocol[];
 }
 
 export enum StreamingProtocol {
@@ -62,6 +85,36 @@ export interface RecordingHistory {
 	fileSize: number;
 	deleted: boolean;
 	liveInfo?: LiveInfo;
+	// 录制时的平台，旧数据没有这个字段
+	platformKind?: PlatformKind | { Custom: string };
+	trash?: TrashInfo;
+	fingerprint?: string;
+}
+
+export interface ScanReport {
+	dryRun: boolean;
+	scanned: number;
+	relinked: RecordingHistory[];
+	imported: RecordingHistory[];
+	missing: RecordingHistory[];
+	skipped: string[];
+}
+
+export interface Relocation {
+	from: string;
+	to: string;
+	copy: boolean;
+}
+
+export interface RelocateProgress {
+	total: number;
+	done: number;
+	path: string;
+}
+
+export interface TrashInfo {
+	path: string;
+	trashedAt: number;
 }
 
 export type RecordingStrategy =
@@ -71,6 +124,69 @@ export type RecordingStrategy =
 	| { kind: 'AnchorLive' }
 	| { kind: 'AnchorLiveWithDuration'; value: [number] };
 
+export interface HistoryQuery {
+	platformKind?: PlatformKind | { Custom: string };
+	anchorName?: string;
+	startFrom?: number;
+	startTo?: number;
+	status?: RecordingStatus;
+	minSize?: number;
+	title?: string;
+	trashed?: boolean;
+	sort?: 'StartTimeDesc' | 'StartTimeAsc' | 'DurationDesc';
+	cursor?: string;
+	limit?: number;
+}
+
+export interface HistoryPage {
+	items: RecordingHistory[];
+	total: number;
+	nextCursor?: string;
+}
+
+export interface LiveSample {
+	time: number;
+	viewerCount: string;
+	viewers?: number;
+	title: string;
+	roomCover: string;
+}
+
+export interface RecordingStats {
+	total: StatsBucket;
+	byAnchor: StatsBucket[];
+	byPlatform: StatsBucket[];
+	byDay: StatsBucket[];
+	byWeek: StatsBucket[];
+	longest: RecordingHistory[];
+}
+
+export interface StatsBucket {
+	key: string;
+	sessions: number;
+	duration: number;
+	bytes: number;
+	failures: number;
+	averageDuration: number;
+}
+
+export interface DbCheckReport {
+	orphanedRecordings: string[];
+	missingFiles: RecordingHistory[];
+	orphanedLives: string[];
+	orphanedSamples: number;
+	brokenIndexEntries: number;
+	repaired: boolean;
+	compactScheduled: boolean;
+}
+
+export interface BackupInfo {
+	name: string;
+	path: string;
+	size: number;
+	createdAt: number;
+}
+
 export interface RecordingPlan {
 	url: string;
 	streamProtocol: string;
@@ -80,14 +196,82 @@ export interface RecordingPlan {
 	updatedAt: number;
 	liveInfo?: LiveInfo;
 	strategy: 'AnchorLive';
+	displayName?: string;
+	// null 表示清除，改回使用分组或默认的选流策略
+	qualityPolicy?: QualityPolicy | null;
+	conditions?: RecordCondition;
+	tags?: string[];
+}
+
+export interface PlanGroup {
+	name: string;
+	option?: RecordingOption;
+	qualityPolicy?: QualityPolicy;
+}
+
+export interface PlanPatch {
+	strategy?: RecordingStrategy;
+	streamProtocol?: StreamingProtocol;
+	streamResolution?: string;
+	enabled?: boolean;
+	option?: RecordingOption;
+	displayName?: string;
+	qualityPolicy?: QualityPolicy;
+	conditions?: RecordCondition;
+	tags?: string[];
+}
+
+export enum PlanFormat {
+	Text = 'Text',
+	Csv = 'Csv',
+	Json = 'Json',
+	UrlConfig = 'UrlConfig'
+}
+
+export interface ImportReport {
+	dryRun: boolean;
+	entries: ImportEntry[];
+}
+
+export interface ImportEntry {
+	line: number;
+	url: string;
+	result: 'Added' | 'Duplicate' | { Error: string };
+}
+
+export interface RecordCondition {
+	titleInclude?: string;
+	titleExclude?: string;
+	category?: string;
+	minViewerCount?: number;
 }
 
 export interface AppConfig {
 	ffmpegPath: string;
 	savePath: string;
 	liveInfoCheckInterval: number;
+	streamlinkPath: string;
+	ytdlpPath: string;
+	userAgentProfiles: { [platform: string]: UserAgentProfile };
+	resolverConcurrency: number;
+	resolverMinInterval: number;
+	liveInfoCacheTtl: number;
+	pollMinInterval: number;
+	pollMaxInterval: number;
+	backupDir: string;
+	backupCount: number;
+	backupInterval: number;
+	trashRetentionDays: number;
+}
+
+export interface PlanSchedule {
+	url: string;
+	nextCheckAt: number;
+	predictedLiveAt?: number;
 }
 
+export type UserAgentProfile = 'Chrome' | 'Firefox' | 'Safari' | 'Mobile' | { Custom: string };
+
 export interface Stream {
 	url: string;
 	resolution: string;
@@ -1,6 +1,7 @@
-import { LiveStatus, PlatformKind, StreamingProtocol, type LiveInfo, type Stream } from '@/model';
+import { LiveStatus, PlatformKind, StreamingProtocol, type LiveInfo } from '@/model';
+import { requestForPlatform } from './request';
 
-export function getLiveInfoForBilibili(url: string, html: string): LiveInfo {
+export async function getLiveInfoForBilibili(url: string): Promise<LiveInfo> {
 	let info: LiveInfo = {
 		url,
 		title: '',
@@ -12,22 +13,99 @@ export function getLiveInfoForBilibili(url: string, html: string): LiveInfo {
 		platformKind: PlatformKind.Bilibili,
 		status: LiveStatus.NotLive
 	};
-	// match window.__NEPTUNE_IS_MY_WAIFU__=(.*?)window.__NEPTUNE_IS_MY_WAIFU__
-	let jsonStr = html.match(/window.__NEPTUNE_IS_MY_WAIFU__=(.*?)window.__NEPTUNE_IS_MY_WAIFU__/);
-	if (!jsonStr || jsonStr.length < 2) {
-		return info;
+	// 房间号可能是短号，例如 https://live.bilibili.com/6，接口会返回真实的房间号
+	let roomId = url.match(/live\.bilibili\.com\/(?:h5\/)?(\d+)/)?.[1];
+	if (!roomId) {
+		throw new Error(`can not find room id in url: ${url}`);
+	}
+	try {
+		// 请求都通过后端发出，登录过的话会带上账号的 cookie，可以拿到原画等需要登录的清晰度
+		let room = await getJson(
+			`https://api.live.bilibili.com/room/v1/Room/get_info?room_id=${roomId}`
+		);
+		info.title = room.title || '';
+		info.roomCover = room.user_cover || '';
+		info.viewerCount = room.online?.toString() || '';
+		info.category = room.area_name || '';
+		let anchor = await getJson(
+			`https://api.live.bilibili.com/live_user/v1/Master/info?uid=${room.uid}`
+		);
+		info.anchorName = anchor.info?.uname || '';
+		info.anchorAvatar = anchor.info?.face || '';
+		// 1: 直播中，0: 未开播，2: 轮播
+		if (room.live_status !== 1) {
+			return info;
+		}
+		let playInfo = await getJson(
+			`https://api.live.bilibili.com/xlive/web-room/v2/index/getRoomPlayInfo?room_id=${room.room_id}&protocol=0,1&format=0,1,2&codec=0,1&qn=10000&platform=web&ptype=8`
+		);
+		fillStreams(playInfo, info);
+		info.status = LiveStatus.Live;
+	} catch (e) {
+		console.error('get live info for bilibili failed: ', e);
+		throw e;
 	}
-	let json = JSON.parse(jsonStr[1]);
-	console.log('bilibili', json);
-
 	return info;
 }
 
-export function getHeadersForKuaishou() {
+// 请求接口，code 不为 0 时抛出错误，返回 data 字段
+async function getJson(api: string): Promise<any> {
+	let json = JSON.parse(await requestForPlatform(PlatformKind.Bilibili, api, getHeaders()));
+	if (json.code !== 0) {
+		throw new Error(`bilibili api error: ${json.code} ${json.message}`);
+	}
+	return json.data;
+}
+
+// 解析 getRoomPlayInfo 的结果，每种协议、封装格式和编码各是一个流
+function fillStreams(playInfo: any, info: LiveInfo) {
+	let playurl = playInfo.playurl_info?.playurl;
+	if (!playurl) {
+		return;
+	}
+	let qnDesc: { [key: number]: string } = {};
+	for (let desc of playurl.g_qn_desc || []) {
+		qnDesc[desc.qn] = desc.desc;
+	}
+	for (let stream of playurl.stream || []) {
+		let protocol =
+			stream.protocol_name === 'http_hls' ? StreamingProtocol.Hls : StreamingProtocol.Flv;
+		for (let format of stream.format || []) {
+			for (let codec of format.codec || []) {
+				let urlInfo = codec.url_info?.[0];
+				if (!urlInfo) {
+					continue;
+				}
+				let resolution = qnDesc[codec.current_qn] || codec.current_qn.toString();
+				// 同一清晰度可能同时有 avc 和 hevc 编码，hevc 的标出来
+				if (codec.codec_name === 'hevc') {
+					resolution = `${resolution} HEVC`;
+				}
+				info.streams.push({
+					url: `${urlInfo.host}${codec.base_url}${urlInfo.extra}`,
+					resolution,
+					protocol,
+					// CDN 会检查 Referer 和 Origin，不带的话返回 403
+					headers: getStreamHeaders()
+				});
+			}
+		}
+	}
+}
+
+// 录制时 ffmpeg 请求流地址使用的请求头
+function getStreamHeaders() {
+	return {
+		Referer: 'https://live.bilibili.com/',
+		Origin: 'https://live.bilibili.com'
+	};
+}
+
+function getHeaders() {
 	return {
 		'User-Agent':
 			'Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:109.0) Gecko/20100101 Firefox/115.0',
-		Accept: 'text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8',
-		Referer: 'https://live.bilibili.com/?spm_id_from=333.1296.0.0'
+		Accept: 'application/json, text/plain, */*',
+		Referer: 'https://live.bilibili.com/'
 	};
 }
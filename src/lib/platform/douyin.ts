@@ -1,7 +1,5 @@
 import { LiveStatus, PlatformKind, StreamingProtocol, type LiveInfo } from '@/model';
-import { getProxyForFetch } from '@/proxy';
-// import { invoke } from '@tauri-apps/api/core';
-import { fetch } from '@tauri-apps/plugin-http';
+import { requestForPlatform } from './request';
 
 export async function getLiveInfoForDouyin(url: string): Promise<LiveInfo> {
 	let info: LiveInfo = {
@@ -17,15 +15,7 @@ export async function getLiveInfoForDouyin(url: string): Promise<LiveInfo> {
 	};
 	try {
 		// 首先请求页面内容
-		const resp = await fetch(url, {
-			method: 'GET',
-			headers: getHeaders(),
-			connectTimeout: 10000,
-			proxy: {
-				all: await getProxyForFetch()
-			}
-		});
-		let html = await resp.text();
+		let html = await requestForPlatform(PlatformKind.Douyin, url, getHeaders());
 		// 解析 html，填充 LiveInfo
 		parseHtmlAndFillLiveInfo(html, info);
 	} catch (e) {
@@ -80,6 +70,7 @@ function parseHtmlAndFillLiveInfo(html: string, info: LiveInfo) {
 	info.title = (room.title as string) || '';
 	info.anchorAvatar = (room.owner.avatar_thumb.url_list[0] as string) || '';
 	info.viewerCount = (room.user_count_str as string) || '';
+	info.category = (room.partition_road_map?.partition?.title as string) || '';
 	let streamUrl = room.stream_url;
 	let flvUrlMap: { [key: string]: string } = streamUrl.flv_pull_url;
 	let hlsUrlMap: { [key: string]: string } = streamUrl.hls_pull_url_map;
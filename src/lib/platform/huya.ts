@@ -1,6 +1,6 @@
 import { LiveStatus, PlatformKind, StreamingProtocol, type LiveInfo } from '@/model';
 import CryptoJS from 'crypto-js';
-import { fetch } from '@tauri-apps/plugin-http';
+import { requestForPlatform } from './request';
 
 export async function getLiveInfoForHuya(url: string): Promise<LiveInfo> {
 	let info: LiveInfo = {
@@ -17,12 +17,7 @@ export async function getLiveInfoForHuya(url: string): Promise<LiveInfo> {
 
 	try {
 		// 首先请求页面内容
-		let resp = await fetch(url, {
-			method: 'GET',
-			headers: getHeaders(),
-			connectTimeout: 10000,
-		});
-		let html = await resp.text();
+		let html = await requestForPlatform(PlatformKind.Huya, url, getHeaders());
 		// 解析 html，填充 LiveInfo
 		parseHtmlAndFillLiveInfo(html, info);
 	} catch (e) {
@@ -47,6 +42,7 @@ function parseHtmlAndFillLiveInfo(html: string, info: LiveInfo) {
 	info.anchorAvatar = gameLiveInfo.avatar180 || '';
 	info.title = gameLiveInfo.introduction || '';
 	info.roomCover = gameLiveInfo.screenshot || '';
+	info.category = gameLiveInfo.gameFullName || '';
 	let selectCdn = gameStreamInfoList[0];
 	let flvUrl = selectCdn.sFlvUrl || '';
 	let streamName = selectCdn.sStreamName || '';
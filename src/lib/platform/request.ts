@@ -0,0 +1,13 @@
+import type { PlatformKind } from '@/model';
+import { invoke } from '@tauri-apps/api/core';
+
+// 通过后端请求平台接口，后端会带上用户保存的账号 cookie，并使用系统代理
+// body 为空时发送 GET 请求，否则发送 POST 请求
+export async function requestForPlatform(
+	platformKind: PlatformKind,
+	url: string,
+	headers: { [key: string]: string },
+	body?: string
+): Promise<string> {
+	return invoke('request_for_platform', { platformKind, url, headers, body });
+}
@@ -1,6 +1,5 @@
 import { LiveStatus, PlatformKind, StreamingProtocol, type LiveInfo, type Stream } from '@/model';
-import { getProxyForFetch } from '@/proxy';
-import { fetch } from '@tauri-apps/plugin-http';
+import { requestForPlatform } from './request';
 
 export async function getLiveInfoForTiktok(url: string): Promise<LiveInfo> {
 	let info: LiveInfo = {
@@ -16,16 +15,7 @@ export async function getLiveInfoForTiktok(url: string): Promise<LiveInfo> {
 	};
 	try {
 		// 首先请求页面内容
-		// let data = await invoke('request', { url, headers: getHeaders() });
-		let resp = await fetch(url, {
-			method: 'GET',
-			headers: getHeaders(),
-			connectTimeout: 10000,
-			proxy: {
-				all: await getProxyForFetch()
-			}
-		});
-		let html = await resp.text();
+		let html = await requestForPlatform(PlatformKind.Tiktok, url, getHeaders());
 		// 解析 html，填充 LiveInfo
 		parseHtmlAndFillLiveInfo(html, info);
 	} catch (e) {
@@ -1,6 +1,5 @@
 import { LiveStatus, PlatformKind, StreamingProtocol, type LiveInfo, type Stream } from '@/model';
-import { getProxyForFetch } from '@/proxy';
-import { fetch } from '@tauri-apps/plugin-http';
+import { requestForPlatform } from './request';
 
 export async function getLiveInfoForTwitch(url: string): Promise<LiveInfo> {
 	let info: LiveInfo = {
@@ -17,14 +16,11 @@ export async function getLiveInfoForTwitch(url: string): Promise<LiveInfo> {
 	// 先找出 uid, 从 url 中提取，例如 https://www.twitch.tv/uid
 	let uid = url.split('/').pop();
 	try {
-		let resp = await fetch('https://gql.twitch.tv/gql', {
-			method: 'POST',
-			headers: getHeadersForStream(),
-			connectTimeout: 10000,
-			proxy: {
-				all: await getProxyForFetch()
-			},
-			body: JSON.stringify({
+		let resp = await requestForPlatform(
+			PlatformKind.Twitch,
+			'https://gql.twitch.tv/gql',
+			getHeadersForStream(),
+			JSON.stringify({
 				operationName: 'PlaybackAccessToken_Template',
 				query:
 					'query PlaybackAccessToken_Template($login: String!, $isLive: Boolean!, $vodID: ID!, $isVod: Boolean!, $playerType: String!) {  streamPlaybackAccessToken(channelName: $login, params: {platform: "web", playerBackend: "mediaplayer", playerType: $playerType}) @include(if: $isLive) {    value    signature   authorization { isForbidden forbiddenReasonCode }   __typename  }  videoPlaybackAccessToken(id: $vodID, params: {platform: "web", playerBackend: "mediaplayer", playerType: $playerType}) @include(if: $isVod) {    value    signature   __typename  }}',
@@ -36,14 +32,15 @@ export async function getLiveInfoForTwitch(url: string): Promise<LiveInfo> {
 					playerType: 'site'
 				}
 			})
-		});
-		let jsonData1 = JSON.parse(await resp.text());
+		);
+		let jsonData1 = JSON.parse(resp);
 		let token = jsonData1.data.streamPlaybackAccessToken.value;
 		let sign = jsonData1.data.streamPlaybackAccessToken.signature;
-		let resp2 = await fetch('https://gql.twitch.tv/gql', {
-			method: 'POST',
-			headers: getHeaders(token),
-			body: JSON.stringify([
+		let resp2 = await requestForPlatform(
+			PlatformKind.Twitch,
+			'https://gql.twitch.tv/gql',
+			getHeaders(token),
+			JSON.stringify([
 				{
 					operationName: 'ChannelShell',
 					variables: {
@@ -57,8 +54,8 @@ export async function getLiveInfoForTwitch(url: string): Promise<LiveInfo> {
 					}
 				}
 			])
-		});
-		let json = JSON.parse(await resp2.text());
+		);
+		let json = JSON.parse(resp2);
 		await parseJsonAndFillLiveInfo(json, info, sign, token);
 	} catch (e) {
 		console.error('get live info for twitch failed: ', e);
@@ -144,15 +141,12 @@ async function parseJsonAndFillLiveInfo(json: any, info: LiveInfo, sign: string,
 
 async function getPlayUrlList(m3u8: string, headers: any) {
 	try {
-		let resp = await fetch(m3u8, {
-			method: 'GET',
-			headers
-		});
+		let resp = await requestForPlatform(PlatformKind.Twitch, m3u8, headers);
 		let playUrlList = [];
 		// 找 GROUP-ID= 字样，这是分辨率
 		let groupPattern = /GROUP-ID="([^"]+)"/;
 		let groupId: string = '';
-		for (let line of (await resp.text()).split('\n')) {
+		for (let line of resp.split('\n')) {
 			if (groupPattern.test(line)) {
 				groupId = line.match(groupPattern)![0];
 			}
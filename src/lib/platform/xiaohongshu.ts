@@ -1,7 +1,6 @@
 import { LiveStatus, PlatformKind, StreamingProtocol, type LiveInfo } from '@/model';
-import { getProxyForFetch } from '@/proxy';
 import { invoke } from '@tauri-apps/api/core';
-import { fetch } from '@tauri-apps/plugin-http';
+import { requestForPlatform } from './request';
 
 export async function getLiveInfoForXiaohongshu(url: string): Promise<LiveInfo> {
 	let info: LiveInfo = {
@@ -21,15 +20,9 @@ export async function getLiveInfoForXiaohongshu(url: string): Promise<LiveInfo>
 	roomId = roomId.split('?')[0].split('/')[0] || '';
 	let appApi = `https://www.xiaohongshu.com/api/sns/red/live/app/v1/ecology/outside/share_info?room_id=${roomId}`;
 	try {
-		let resp = await fetch(appApi, {
-			method: 'GET',
-			headers: getHeaders(),
-			connectTimeout: 10000,
-			proxy: {
-				all: await getProxyForFetch()
-			}
-		});
-		let json = JSON.parse(await resp.text());
+		let json = JSON.parse(
+			await requestForPlatform(PlatformKind.Xiaohongshu, appApi, getHeaders())
+		);
 		if (json.code != 0) {
 			console.error('xiaohongshu api error', json);
 			return info;
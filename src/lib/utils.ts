@@ -1,4 +1,6 @@
+import { invoke } from '@tauri-apps/api/core';
 import { PlatformKind, type LiveInfo } from './model';
+import { getLiveInfoForBilibili } from './platform/bilibili';
 import { getLiveInfoForDouyin } from './platform/douyin';
 import { getLiveInfoForHuya } from './platform/huya';
 import { getLiveInfoForKuaishou } from './platform/kuaishou';
@@ -63,7 +65,11 @@ export function openDialog(id: string) {
 	dialog?.showModal();
 }
 
-export function getPlatformIcon(platformKind: string): string {
+export function getPlatformIcon(platformKind: string | { Custom: string }): string {
+	// 自定义平台没有图标，使用本地的通用图标，不请求外部服务
+	if (typeof platformKind !== 'string') {
+		return '/platform.svg';
+	}
 	switch (platformKind.toLowerCase()) {
 		case 'douyin':
 			return '/douyin.ico';
@@ -84,7 +90,7 @@ export function getPlatformIcon(platformKind: string): string {
 		case 'tiktok':
 			return '/tiktok.ico';
 		default:
-			return 'https://www.google.com/favicon.ico';
+			return '/platform.svg';
 	}
 }
 
@@ -100,6 +106,8 @@ export function getPlatformKind(url: string): PlatformKind {
 			return PlatformKind.Xiaohongshu;
 		case url.startsWith('https://www.huya.com/'):
 			return PlatformKind.Huya;
+		case url.startsWith('https://live.bilibili.com/'):
+			return PlatformKind.Bilibili;
 		case url.startsWith('https://www.twitch.tv/'):
 			return PlatformKind.Twitch;
 		case url.startsWith('https://www.youtube.com/watch?v='):
@@ -127,7 +135,10 @@ export async function getLiveInfoForPlatform(url: string): Promise<LiveInfo> {
 			return getLiveInfoForXiaohongshu(url);
 		case PlatformKind.Kuaishou:
 			return getLiveInfoForKuaishou(url);
+		case PlatformKind.Bilibili:
+			return getLiveInfoForBilibili(url);
 		default:
-			throw new Error('unknown platform');
+			// 前端没有实现的平台，交给后端的解析器，例如自定义平台
+			return invoke('get_live_info', { url });
 	}
 }